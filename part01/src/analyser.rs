@@ -1 +1,11 @@
-pub mod dependency_analyser_lib;
\ No newline at end of file
+pub mod api_surface;
+pub mod call_graph;
+pub mod config;
+pub mod dependency_analyser_lib;
+pub mod graph;
+pub mod index;
+pub mod options;
+pub mod rules;
+pub mod sink;
+pub mod suppressions;
+pub mod workspace;
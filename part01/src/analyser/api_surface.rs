@@ -0,0 +1,53 @@
+use std::collections::{BTreeMap, BTreeSet};
+use serde::Serialize;
+use crate::analyser::graph::ClassGraph;
+
+/// A class used from outside its own package.
+#[derive(Debug, Clone, Serialize)]
+pub struct ExposedClass {
+    pub class: String,
+    /// Packages referencing the class
+    pub used_by: Vec<String>,
+}
+
+/// De-facto public API of a package: the classes other packages reference,
+/// against the ones only used internally (or not used at all).
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageApiSurface {
+    pub package: String,
+    pub exposed: Vec<ExposedClass>,
+    pub internal: Vec<String>,
+}
+
+pub fn api_surface(graph: &ClassGraph) -> Vec<PackageApiSurface> {
+    let mut used_by: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for (from, to) in graph.edges.keys() {
+        if !graph.is_project_class(to) {
+            continue;
+        }
+        let (from_package, to_package) = (graph.package_of(from), graph.package_of(to));
+        if from_package != to_package {
+            used_by.entry(to.as_str()).or_default().insert(from_package);
+        }
+    }
+
+    graph.packages().into_iter()
+        .map(|package| {
+            let mut surface = PackageApiSurface {
+                package: package.to_string(),
+                exposed: Vec::new(),
+                internal: Vec::new(),
+            };
+            for (class, _) in graph.classes.iter().filter(|(_, p)| p.as_str() == package) {
+                match used_by.get(class.as_str()) {
+                    Some(packages) => surface.exposed.push(ExposedClass {
+                        class: class.clone(),
+                        used_by: packages.iter().map(|p| p.to_string()).collect(),
+                    }),
+                    None => surface.internal.push(class.clone()),
+                }
+            }
+            surface
+        })
+        .collect()
+}
@@ -0,0 +1,84 @@
+use std::collections::BTreeSet;
+use serde::Serialize;
+use crate::analyser::index::{flatten_classes, ProjectIndex};
+use crate::common::types::ClassDepsReport;
+
+/// A call from one project method to another, both written as `pkg.Class.method`.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct CallEdge {
+    pub caller: String,
+    pub callee: String,
+}
+
+/// Calls between the classes of the project, built from reports analysed with
+/// `AnalysisOptions::call_graph` set. Calls to types outside the project are
+/// left out, and overloads of a method share the same node.
+#[derive(Debug, Default, Serialize)]
+pub struct CallGraph {
+    pub edges: BTreeSet<CallEdge>,
+}
+
+impl CallGraph {
+    pub fn build(classes: &[ClassDepsReport]) -> CallGraph {
+        let index = ProjectIndex::new(classes);
+        let mut graph = CallGraph::default();
+        for (class, top_level) in flatten_classes(classes) {
+            let class_name = class.qualified_name();
+            for call in &class.calls {
+                let callee_class = match &call.receiver {
+                    Some(receiver) => index.resolve(class, top_level, receiver),
+                    None => class_name.clone(),
+                };
+                if index.contains(&callee_class) {
+                    graph.edges.insert(CallEdge {
+                        caller: format!("{}.{}", class_name, call.caller),
+                        callee: format!("{}.{}", callee_class, call.method),
+                    });
+                }
+            }
+        }
+        graph
+    }
+
+    pub fn callees_of(&self, method: &str) -> Vec<&str> {
+        self.edges.iter()
+            .filter(|edge| edge.caller == method)
+            .map(|edge| edge.callee.as_str())
+            .collect()
+    }
+
+    pub fn callers_of(&self, method: &str) -> Vec<&str> {
+        self.edges.iter()
+            .filter(|edge| edge.callee == method)
+            .map(|edge| edge.caller.as_str())
+            .collect()
+    }
+
+    /// Every method reaching `method` through a chain of calls, i.e. what may be
+    /// affected when `method` changes.
+    pub fn impacted_by(&self, method: &str) -> BTreeSet<String> {
+        let mut impacted = BTreeSet::new();
+        let mut pending = vec![method.to_string()];
+        while let Some(current) = pending.pop() {
+            for caller in self.callers_of(&current) {
+                if impacted.insert(caller.to_string()) {
+                    pending.push(caller.to_string());
+                }
+            }
+        }
+        impacted
+    }
+
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph calls {\n");
+        for edge in &self.edges {
+            dot.push_str(&format!("    \"{}\" -> \"{}\";\n", edge.caller, edge.callee));
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    pub fn to_json(&self) -> Result<String, String> {
+        serde_json::to_string_pretty(self).map_err(|e| format!("Failed to serialise the call graph: {}", e))
+    }
+}
@@ -0,0 +1,160 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use serde::Deserialize;
+use crate::analyser::options::AnalysisOptions;
+use crate::common::format::ReportFormat;
+
+pub const CONFIG_FILE_NAME: &str = ".depanalyzer.toml";
+
+/// Project-local settings read from `.depanalyzer.toml`:
+///
+/// ```toml
+/// exclude = ["**/generated/**"]
+/// extra_extensions = ["jav"]
+/// stdlib_prefixes = ["java", "javax"]
+/// max_file_size = 1048576
+/// edge_rules = [
+///     "com.foo.domain must-not-depend-on com.foo.web",
+///     "com.foo.domain.legacy may-depend-on com.foo.web.dto",
+/// ]
+///
+/// [[layers]]
+/// name = "web"
+/// packages = ["com.foo.web"]
+///
+/// [[layers]]
+/// name = "domain"
+/// packages = ["com.foo.domain"]
+///
+/// [thresholds]
+/// max_class_dependencies = 20
+///
+/// [output]
+/// stream = false
+/// format = "plain"
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ProjectConfig {
+    /// Globs, relative to the source root, of the files to leave out
+    pub exclude: Vec<String>,
+    /// Extensions besides `java` of the files to analyse
+    pub extra_extensions: Vec<String>,
+    /// Packages whose types are not reported as dependencies
+    pub stdlib_prefixes: Vec<String>,
+    /// Bytes above which a file is skipped, 10 MiB when not set
+    pub max_file_size: Option<u64>,
+    /// Layers from the top down, a layer may only depend on itself and the ones below
+    pub layers: Vec<Layer>,
+    /// Forbidden edges between packages or classes, with their exceptions
+    pub edge_rules: Vec<EdgeRule>,
+    pub thresholds: Thresholds,
+    pub output: OutputDefaults,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Layer {
+    pub name: String,
+    /// Package prefixes belonging to the layer
+    pub packages: Vec<String>,
+}
+
+/// `<from> must-not-depend-on <to>` forbids the edges between the two name
+/// prefixes, `<from> may-depend-on <to>` allows some of them back.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub struct EdgeRule {
+    pub from: String,
+    pub to: String,
+    pub allowed: bool,
+}
+
+impl TryFrom<String> for EdgeRule {
+    type Error = String;
+
+    fn try_from(rule: String) -> Result<Self, Self::Error> {
+        let words: Vec<&str> = rule.split_whitespace().collect();
+        let allowed = match words.as_slice() {
+            [_, "must-not-depend-on", _] => false,
+            [_, "may-depend-on", _] => true,
+            _ => return Err(format!(
+                "Invalid edge rule '{}', expected '<from> must-not-depend-on <to>' or '<from> may-depend-on <to>'",
+                rule
+            )),
+        };
+        Ok(EdgeRule { from: words[0].to_string(), to: words[2].to_string(), allowed })
+    }
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Thresholds {
+    /// Classes using more distinct types than this are reported
+    pub max_class_dependencies: Option<usize>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct OutputDefaults {
+    pub stream: bool,
+    pub format: ReportFormat,
+}
+
+impl ProjectConfig {
+    pub fn load(path: &Path) -> Result<ProjectConfig, String> {
+        let contents = match fs::read_to_string(path) {
+            Ok(contents) => contents,
+            Err(e) => return Err(format!("Failed to read {}: {}", path.display(), e)),
+        };
+        toml::from_str(&contents).map_err(|e| format!("Invalid {}: {}", path.display(), e))
+    }
+
+    /// Looks for the config file in `root` and then in its ancestors, so that a
+    /// file at the repository top applies to `src/main/java` as well.
+    pub fn discover(root: &Path) -> Result<Option<(PathBuf, ProjectConfig)>, String> {
+        let root = root.canonicalize().unwrap_or_else(|_| root.to_path_buf());
+        for dir in root.ancestors() {
+            let candidate = dir.join(CONFIG_FILE_NAME);
+            if candidate.is_file() {
+                return ProjectConfig::load(&candidate).map(|config| Some((candidate, config)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Whether any layer, edge rule or threshold is configured.
+    pub fn has_rules(&self) -> bool {
+        !self.layers.is_empty() || !self.edge_rules.is_empty() || self.thresholds.max_class_dependencies.is_some()
+    }
+
+    pub fn apply(&self, options: &mut AnalysisOptions) {
+        options.exclude.extend(self.exclude.iter().cloned());
+        options.extra_extensions.extend(self.extra_extensions.iter().cloned());
+        options.stdlib_prefixes.extend(self.stdlib_prefixes.iter().cloned());
+        if self.max_file_size.is_some() {
+            options.max_file_size = self.max_file_size;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn documented_example_parses() {
+        let source = include_str!("config.rs");
+        let example: String = source
+            .lines()
+            .skip_while(|line| *line != "/// ```toml")
+            .skip(1)
+            .take_while(|line| *line != "/// ```")
+            .map(|line| format!("{}\n", line.trim_start_matches("///").strip_prefix(' ').unwrap_or("")))
+            .collect();
+        let config: ProjectConfig = toml::from_str(&example).unwrap();
+        assert_eq!(config.layers.len(), 2);
+        assert_eq!(config.edge_rules.len(), 2);
+        assert_eq!(config.max_file_size, Some(1048576));
+    }
+}
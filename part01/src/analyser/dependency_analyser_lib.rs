@@ -1,75 +1,218 @@
+use std::collections::{BTreeSet, HashMap};
 use std::fs::read_dir;
-use crate::common::types::{ClassDepsReport, PackageDepsReport, ProjectDepsReport};
-use tokio::{fs::File, io::AsyncReadExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::analyser::index::{flatten_classes, ProjectIndex};
+use crate::analyser::options::{is_excluded, AnalysisOptions};
+use crate::analyser::sink::{DiscardSink, ReportSink};
+use crate::analyser::suppressions::Suppressions;
+use crate::common::types::{
+    AccessModifier, ClassDepsReport, ClassSize, Dependency, DependencyKind, FileDiagnostic, MethodCall, MethodDepsReport,
+    PackageDepsReport, ProjectDepsReport, TypeKind,
+};
+use tokio::{fs::File, io::AsyncReadExt, sync::Semaphore, task::JoinSet};
 use tree_sitter::{Parser, Language, Node};
 use walkdir::WalkDir;
 
 pub async fn get_class_dependencies(class_src_file: String) -> Result<Vec<ClassDepsReport>, String> {
-    let mut file = match File::open(class_src_file).await {
-        Ok(file) => file,
-        Err(e) => return Err(format!("Failed to open file: {}", e)),
-    };
+    get_class_dependencies_with_options(class_src_file, &AnalysisOptions::default()).await
+}
 
-    let mut contents = String::new();
-    match file.read_to_string(&mut contents).await {
-        Ok(_) => (),
-        Err(e) => return Err(format!("Failed to read file: {}", e)),
-    };
+pub async fn get_class_dependencies_with_options(class_src_file: String, options: &AnalysisOptions) -> Result<Vec<ClassDepsReport>, String> {
+    let contents = read_source(&class_src_file, options).await?;
+    Ok(analyse_source(&contents, &class_src_file, options))
+}
 
+/// Reports of the classes declared in `contents`, read from `class_src_file`.
+pub(crate) fn analyse_source(contents: &str, class_src_file: &str, options: &AnalysisOptions) -> Vec<ClassDepsReport> {
     // Create a Tree-sitter parser and set the Java language.
     let mut parser = Parser::new();
     let language: Language = Language::from(tree_sitter_java::LANGUAGE);
     parser.set_language(&language)
         .expect("Error loading Java grammar");
 
-    let tree = parser.parse(&contents, None)
+    let tree = parser.parse(contents, None)
         .expect("Failed to parse the Java source");
 
     let root = tree.root_node();
 
-    let classes = collect_all_classes(&root, &contents);
+    let package = collect_package(&root, contents);
+    let mut classes = collect_all_classes(&root, contents, &package, &[], class_src_file, options);
+    let mut suppressions = Suppressions::collect(&root, contents);
+    suppressions.ignore_packages(&options.stdlib_prefixes);
+    if !suppressions.is_empty() {
+        let imports: Vec<String> = collect_file_imports(&root, contents).into_iter()
+            .map(|import| import.target)
+            .collect();
+        apply_suppressions(&mut classes, &suppressions, &package, &imports);
+    }
+    if options.snippets {
+        attach_snippets(&mut classes, &contents.lines().collect::<Vec<&str>>());
+    }
+    annotate_internal_targets(&mut classes);
+    classes
+}
+
+async fn read_source(class_src_file: &str, options: &AnalysisOptions) -> Result<String, String> {
+    let mut file = match File::open(class_src_file).await {
+        Ok(file) => file,
+        Err(e) => return Err(format!("Failed to open file: {}", e)),
+    };
 
-    Ok(classes)
+    if let Some(limit) = options.max_file_size {
+        match file.metadata().await {
+            Ok(meta) if meta.len() > limit => {
+                return Err(format!("File is {} bytes, above the {} bytes limit", meta.len(), limit))
+            },
+            Ok(_) => (),
+            Err(e) => return Err(format!("Failed to read file metadata: {}", e)),
+        }
+    }
+
+    let mut contents = String::new();
+    match file.read_to_string(&mut contents).await {
+        Ok(_) => Ok(contents),
+        Err(e) => Err(format!("Failed to read file: {}", e)),
+    }
 }
 
-fn collect_all_classes(node: &Node, code: &str) -> Vec<ClassDepsReport> {
+fn collect_package(root: &Node, code: &str) -> String {
+    for i in 0..root.named_child_count() {
+        let child = root.named_child(i).unwrap();
+        if child.kind() == "package_declaration" {
+            for j in 0..child.named_child_count() {
+                let name = child.named_child(j).unwrap();
+                if name.kind() == "scoped_identifier" || name.kind() == "identifier" {
+                    return name.utf8_text(code.as_bytes()).unwrap().to_string();
+                }
+            }
+        }
+    }
+    String::new()
+}
+
+fn collect_all_classes(
+    node: &Node,
+    code: &str,
+    package: &str,
+    enclosing: &[String],
+    source_file: &str,
+    options: &AnalysisOptions,
+) -> Vec<ClassDepsReport> {
     let mut classes = Vec::new();
 
     // Iterate only over *named* children of `node`
     for i in 0..node.named_child_count() {
         let child = node.named_child(i).unwrap();
 
-        if child.kind() == "class_declaration" {
-            let name_node = child
-                .child_by_field_name("name")
-                .expect("class_declaration without name");
-            let class_name = name_node
-                .utf8_text(code.as_bytes())
-                .expect("Failed to read class name")
-                .to_string();
-
-            // Recurse into the body to find its direct nested classes
-            let nested = if let Some(body) = child.child_by_field_name("body") {
-                collect_all_classes(&body, code)
-            } else {
-                Vec::new()
-            };
+        let type_kind = match child.kind() {
+            "class_declaration" if has_modifier(&child, "abstract") => TypeKind::AbstractClass,
+            "class_declaration" => TypeKind::Class,
+            "interface_declaration" => TypeKind::Interface,
+            _ => continue,
+        };
 
-            // gather in-class deps
-            let file_dependencies = collect_file_imports(&node, code);
-            let class_dependencies = filter_dependencies(collect_class_dependencies(&child, code));
-            classes.push(ClassDepsReport {
-                class_name,
-                class_deps: [file_dependencies, class_dependencies].concat(),
-                nested_classes: nested,
-            });
-        }
+        let name_node = child
+            .child_by_field_name("name")
+            .expect("class_declaration without name");
+        let class_name = name_node
+            .utf8_text(code.as_bytes())
+            .expect("Failed to read class name")
+            .to_string();
+
+        // Recurse into the body to find its direct nested classes
+        let nested = if let Some(body) = child.child_by_field_name("body") {
+            let mut chain = enclosing.to_vec();
+            chain.push(class_name.clone());
+            collect_all_classes(&body, code, package, &chain, source_file, options)
+        } else {
+            Vec::new()
+        };
+
+        // gather in-class deps
+        let mut edges = collect_file_imports(node, code);
+        let (mut class_edges, methods) = collect_class_dependencies(&child, type_kind, options.per_method, code);
+        edges.append(&mut class_edges);
+        let edges = filter_dependencies(edges);
+        classes.push(ClassDepsReport {
+            class_name,
+            package_name: package.to_string(),
+            enclosing_classes: enclosing.to_vec(),
+            type_kind,
+            class_deps: dependency_names(&edges),
+            edges,
+            nested_classes: nested,
+            methods,
+            calls: match options.call_graph {
+                true => collect_class_calls(&child, code),
+                false => Vec::new(),
+            },
+            size: class_size(&child),
+            source_file: source_file.to_string(),
+            source_root: None,
+        });
     }
 
     classes
 }
 
-fn collect_file_imports(root: &Node, code: &str) -> Vec<String> {
+/// Sets on every edge the source line it comes from, `lines` being the ones of the file.
+fn attach_snippets(classes: &mut [ClassDepsReport], lines: &[&str]) {
+    let snippet = |line: usize| lines.get(line - 1).map(|line| line.trim().to_string());
+    for class in classes {
+        for edge in class.edges.iter_mut() {
+            edge.snippet = snippet(edge.line);
+        }
+        for method in class.methods.iter_mut() {
+            for dep in method.deps.iter_mut() {
+                dep.snippet = snippet(dep.line);
+            }
+        }
+        attach_snippets(&mut class.nested_classes, lines);
+    }
+}
+
+fn class_size(class_node: &Node) -> ClassSize {
+    let mut size = ClassSize {
+        lines: lines_of_code(class_node),
+        ..ClassSize::default()
+    };
+    if let Some(body) = class_node.child_by_field_name("body") {
+        for i in 0..body.named_child_count() {
+            let member = body.named_child(i).unwrap();
+            match member.kind() {
+                "method_declaration" => size.methods += 1,
+                "field_declaration" | "constant_declaration" => {
+                    let mut cursor = member.walk();
+                    size.fields += member.children_by_field_name("declarator", &mut cursor).count();
+                },
+                _ => (),
+            }
+        }
+    }
+    size
+}
+
+/// Counts the lines with at least one token outside comments.
+fn lines_of_code(node: &Node) -> usize {
+    fn mark_rows(node: Node, rows: &mut BTreeSet<usize>) {
+        match node.kind() {
+            "line_comment" | "block_comment" => (),
+            _ if node.child_count() == 0 => rows.extend(node.start_position().row..=node.end_position().row),
+            _ => {
+                for i in 0..node.child_count() {
+                    mark_rows(node.child(i).unwrap(), rows);
+                }
+            },
+        }
+    }
+
+    let mut rows = BTreeSet::new();
+    mark_rows(*node, &mut rows);
+    rows.len()
+}
+
+fn collect_file_imports(root: &Node, code: &str) -> Vec<Dependency> {
     let mut dependencies = Vec::new();
 
     for i in 0..root.named_child_count() {
@@ -100,107 +243,660 @@ fn collect_file_imports(root: &Node, code: &str) -> Vec<String> {
                     path = format!("static {}", path);
                 }
 
-                dependencies.push(path);
+                dependencies.push(Dependency {
+                    target: path,
+                    kind: DependencyKind::Import,
+                    access: None,
+                    target_kind: None,
+                    line: child.start_position().row + 1,
+                    snippet: None,
+                });
             }
         }
     }
     dependencies
 }
 
-fn collect_class_dependencies(class_node: &Node, code: &str) -> Vec<String> {
+/// Returns the class dependencies and, when `per_method` is set, the share of
+/// them coming from each method and constructor.
+fn collect_class_dependencies(
+    class_node: &Node,
+    type_kind: TypeKind,
+    per_method: bool,
+    code: &str,
+) -> (Vec<Dependency>, Vec<MethodDepsReport>) {
     let mut deps = Vec::new();
+    let mut methods = Vec::new();
+
+    let class_access = access_modifier(class_node, AccessModifier::PackagePrivate);
+    // interface members are implicitly public
+    let member_default = match type_kind {
+        TypeKind::Interface => AccessModifier::Public,
+        _ => AccessModifier::PackagePrivate,
+    };
 
     // 1. extends
     if let Some(superc) = class_node.child_by_field_name("superclass") {
-        let n = superc.child_by_field_name("name").unwrap_or(superc);
-        deps.push(n.utf8_text(code.as_bytes()).unwrap().to_string());
+        if let Some(n) = superc.named_child(0) {
+            deps.push(new_dependency(n, DependencyKind::Extends, class_access, code));
+        }
     }
 
-    // 2. implements
-    if let Some(interfaces) = class_node.child_by_field_name("super_interfaces") {
-        for j in 0..interfaces.named_child_count() {
-            let iface = interfaces.named_child(j).unwrap();
-            deps.push(iface.utf8_text(code.as_bytes()).unwrap().to_string());
+    // 2. implements, or extends for interfaces
+    for j in 0..class_node.named_child_count() {
+        let child = class_node.named_child(j).unwrap();
+        let kind = match child.kind() {
+            "super_interfaces" => DependencyKind::Implements,
+            "extends_interfaces" => DependencyKind::Extends,
+            _ => continue,
+        };
+        if let Some(types) = child.named_child(0) {
+            for t in 0..types.named_child_count() {
+                deps.push(new_dependency(types.named_child(t).unwrap(), kind, class_access, code));
+            }
         }
     }
 
     // 3. fields, methods, params, new expressions
     let cursor = class_node.child_by_field_name("body").expect("no body");
+    let return_types = method_return_types(&cursor, code);
+    let field_types = field_types(&cursor, code);
 
     for i in 0..cursor.child_count() {
         let nd = match cursor.child(i) {
             Some(x) => x,
             None => continue,
         };
+        let access = access_modifier(&nd, member_default);
+        let member_start = deps.len();
         match nd.kind() {
-            "field_declaration"
-            | "constructor_declaration"
-            | "object_creation_expression" => {
-                deps = get_and_add_dep_from_child_name(nd, String::from("type"), deps, code);
+            "field_declaration" | "constant_declaration" => {
+                add_typed_dependencies(nd, DependencyKind::Field, access, &mut deps, code);
             },
-            "method_declaration" => {
+            "constructor_declaration" | "method_declaration" => {
+                add_parameter_dependencies(nd, access, &mut deps, code);
+                if nd.kind() == "method_declaration" {
+                    add_typed_dependencies(nd, DependencyKind::ReturnType, access, &mut deps, code);
+                }
+                if let Some(meth_body) = nd.child_by_field_name("body") {
+                    let mut variables = field_types.clone();
+                    declare_parameters(nd, &mut variables, code);
+                    add_body_dependencies(meth_body, access, &return_types, &mut variables, &mut deps, code);
+                }
+            },
+            _ => {}
+        }
+        collect_reflective_dependencies(nd, access, &mut deps, code);
 
-                if let Some(p) = nd.child_by_field_name("parameters")
-                {
-                    for x in 0..p.child_count() {
-                        if p.child(x).unwrap().kind() == "formal_parameter" {
-                            deps = get_and_add_dep_from_child_name(p.child(x).unwrap(), String::from("type"), deps, code);
-                        }
+        if per_method && (nd.kind() == "method_declaration" || nd.kind() == "constructor_declaration") {
+            let mut method_deps = filter_dependencies(deps[member_start..].to_vec());
+            dedup_dependencies(&mut method_deps);
+            methods.push(MethodDepsReport {
+                signature: method_signature(nd, code),
+                deps: method_deps,
+            });
+        }
+    }
+
+    dedup_dependencies(&mut deps);
+    (deps, methods)
+}
+
+/// Keeps one edge per target, kind and access, the one found first in the source.
+fn dedup_dependencies(deps: &mut Vec<Dependency>) {
+    deps.sort_by(|a, b| (&a.target, a.kind, a.access, a.line).cmp(&(&b.target, b.kind, b.access, b.line)));
+    deps.dedup_by(|a, b| a.target == b.target && a.kind == b.kind && a.access == b.access);
+}
+
+/// Invocations made by the methods and constructors of the class whose receiver
+/// type can be told from the source: calls on `this`, on fields, parameters and
+/// local variables, on `new X()` and static calls on a capitalised name.
+fn collect_class_calls(class_node: &Node, code: &str) -> Vec<MethodCall> {
+    let Some(body) = class_node.child_by_field_name("body") else {
+        return Vec::new();
+    };
+
+    let return_types = method_return_types(&body, code);
+    let fields = field_types(&body, code);
+
+    let mut calls = Vec::new();
+    for i in 0..body.named_child_count() {
+        let member = body.named_child(i).unwrap();
+        if member.kind() != "method_declaration" && member.kind() != "constructor_declaration" {
+            continue;
+        }
+        let caller = member.child_by_field_name("name")
+            .map(|n| n.utf8_text(code.as_bytes()).unwrap().to_string())
+            .unwrap_or_default();
+
+        let mut scope = fields.clone();
+        declare_parameters(member, &mut scope, code);
+        if let Some(method_body) = member.child_by_field_name("body") {
+            collect_calls(method_body, &caller, &return_types, &mut scope, &fields, &mut calls, code);
+        }
+    }
+
+    calls.sort();
+    calls.dedup();
+    calls
+}
+
+/// Walks a method body in source order, so locals are known before being used.
+fn collect_calls<'a>(
+    node: Node<'a>,
+    caller: &str,
+    return_types: &HashMap<&'a str, Option<Node<'a>>>,
+    scope: &mut VariableTypes<'a>,
+    fields: &VariableTypes<'a>,
+    calls: &mut Vec<MethodCall>,
+    code: &'a str,
+) {
+    if is_literal_or_comment(&node) {
+        return;
+    }
+    match node.kind() {
+        "class_declaration" | "interface_declaration" | "enum_declaration" | "record_declaration" => return,
+        "local_variable_declaration" | "enhanced_for_statement" | "resource" => {
+            declare_variables(node, return_types, scope, code);
+        },
+        "method_invocation" => {
+            let receiver = match node.child_by_field_name("object") {
+                None => Some(None),
+                Some(object) => receiver_type(object, scope, fields, code).map(Some),
+            };
+            if let (Some(receiver), Some(method)) = (receiver, node.child_by_field_name("name")) {
+                calls.push(MethodCall {
+                    caller: caller.to_string(),
+                    receiver,
+                    method: method.utf8_text(code.as_bytes()).unwrap().to_string(),
+                });
+            }
+        },
+        _ => (),
+    }
+
+    for i in 0..node.named_child_count() {
+        collect_calls(node.named_child(i).unwrap(), caller, return_types, scope, fields, calls, code);
+    }
+}
+
+fn receiver_type(
+    object: Node,
+    scope: &VariableTypes,
+    fields: &VariableTypes,
+    code: &str,
+) -> Option<String> {
+    let text = object.utf8_text(code.as_bytes()).ok()?;
+    match object.kind() {
+        "identifier" => match scope.get(text) {
+            Some(t) => Some(t.utf8_text(code.as_bytes()).ok()?.to_string()),
+            // not a variable, most likely a static call on a class
+            None if text.starts_with(char::is_uppercase) => Some(text.to_string()),
+            None => None,
+        },
+        "field_access" => {
+            let target = object.child_by_field_name("object")?;
+            let field = object.child_by_field_name("field")?.utf8_text(code.as_bytes()).ok()?;
+            match target.kind() {
+                "this" => Some(fields.get(field)?.utf8_text(code.as_bytes()).ok()?.to_string()),
+                _ => None,
+            }
+        },
+        "object_creation_expression" => {
+            Some(object.child_by_field_name("type")?.utf8_text(code.as_bytes()).ok()?.to_string())
+        },
+        "parenthesized_expression" => {
+            let inner = object.named_child(0)?;
+            match inner.kind() {
+                "cast_expression" => Some(inner.child_by_field_name("type")?.utf8_text(code.as_bytes()).ok()?.to_string()),
+                _ => receiver_type(inner, scope, fields, code),
+            }
+        },
+        _ => None,
+    }
+}
+
+/// Name followed by the parameter types, e.g. `m(E, List<String>)`.
+fn method_signature(node: Node, code: &str) -> String {
+    let name = node.child_by_field_name("name")
+        .map(|n| n.utf8_text(code.as_bytes()).unwrap())
+        .unwrap_or("<unnamed>");
+    let mut types = Vec::new();
+    if let Some(p) = node.child_by_field_name("parameters") {
+        for x in 0..p.named_child_count() {
+            let param = p.named_child(x).unwrap();
+            match param.child_by_field_name("type") {
+                Some(t) => types.push(t.utf8_text(code.as_bytes()).unwrap()),
+                // varargs keep the type as first named child
+                None if param.kind() == "spread_parameter" => {
+                    if let Some(t) = param.named_child(0) {
+                        types.push(t.utf8_text(code.as_bytes()).unwrap());
                     }
-                }
+                },
+                None => (),
+            }
+        }
+    }
+    format!("{}({})", name, types.join(", "))
+}
 
-                deps = get_and_add_dep_from_child_name(nd, String::from("type"), deps, code);
-                if let Some(meth_body) = nd.child_by_field_name("body") {
-                    for j in 0..meth_body.child_count() {
-                        let body_field = match meth_body.child(j) {
-                            Some(x) => x,
-                            None => continue,
-                        };
-                        match body_field.kind() {
-                           "local_variable_declaration"
-                            | "return_statement" => {
-                               deps = get_and_add_dep_from_child_name(body_field, String::from("type"), deps, code);
-                            },
-                            "expression_statement" => {
-                                for i in 0..body_field.child_count() {
-                                    let expression_node = body_field.child(i).unwrap();
-                                    if expression_node.kind() == "method_invocation" {
-                                        for j in 0..expression_node.child_count() {
-                                            let obj_creation_node = expression_node.child(j).unwrap();
-                                            if obj_creation_node.kind() == "object_creation_expression" {
-                                                deps = get_and_add_dep_from_child_name(obj_creation_node, String::from("type"), deps, code);
-                                            }
-                                        }
-                                    }
-                                }
-                            }
-                            _ => ()
+/// Looks anywhere below `node` for `X.class` literals and for `Class.forName` /
+/// `loadClass` calls whose argument is a plain string literal. Nested type
+/// declarations are skipped as they get their own report.
+fn collect_reflective_dependencies(node: Node, access: AccessModifier, deps: &mut Vec<Dependency>, code: &str) {
+    if is_literal_or_comment(&node) {
+        return;
+    }
+    match node.kind() {
+        "class_declaration" | "interface_declaration" | "enum_declaration" | "record_declaration" => return,
+        "class_literal" => {
+            if let Some(t) = node.named_child(0) {
+                deps.push(new_dependency(t, DependencyKind::Reflective, access, code));
+            }
+        },
+        "method_invocation" => {
+            if let Some(target) = reflective_invocation_target(node, code) {
+                deps.push(Dependency {
+                    target,
+                    kind: DependencyKind::Reflective,
+                    access: Some(access),
+                    target_kind: None,
+                    line: node.start_position().row + 1,
+                    snippet: None,
+                });
+            }
+        },
+        _ => (),
+    }
+
+    for i in 0..node.named_child_count() {
+        collect_reflective_dependencies(node.named_child(i).unwrap(), access, deps, code);
+    }
+}
+
+fn reflective_invocation_target(invocation: Node, code: &str) -> Option<String> {
+    let name = invocation.child_by_field_name("name")?.utf8_text(code.as_bytes()).ok()?;
+    match name {
+        "forName" => {
+            let object = invocation.child_by_field_name("object")?.utf8_text(code.as_bytes()).ok()?;
+            if object != "Class" && object != "java.lang.Class" {
+                return None;
+            }
+        },
+        // any receiver, usually `getClass().getClassLoader()` or a stored loader
+        "loadClass" => (),
+        _ => return None,
+    }
+
+    // a single plain fragment: no escapes, no text block spanning lines
+    let argument = invocation.child_by_field_name("arguments")?.named_child(0)?;
+    if argument.kind() != "string_literal" || argument.named_child_count() != 1 {
+        return None;
+    }
+    let literal = argument.named_child(0)?;
+    if literal.kind() != "string_fragment" {
+        return None;
+    }
+    let literal = literal.utf8_text(code.as_bytes()).ok()?;
+    if is_qualified_name(literal) { Some(literal.to_string()) } else { None }
+}
+
+/// Whether the node is a literal or a comment, whose text may look like code
+/// but never creates a dependency.
+fn is_literal_or_comment(node: &Node) -> bool {
+    matches!(
+        node.kind(),
+        "string_literal" | "character_literal" | "line_comment" | "block_comment"
+    )
+}
+
+/// `pkg.Outer$Inner` style names, as accepted by `Class.forName`.
+fn is_qualified_name(name: &str) -> bool {
+    !name.is_empty() && name.split('.').all(|part| {
+        let mut chars = part.chars();
+        chars.next().is_some_and(|c| c.is_alphabetic() || c == '_' || c == '$')
+            && chars.all(|c| c.is_alphanumeric() || c == '_' || c == '$')
+    })
+}
+
+fn add_parameter_dependencies(node: Node, access: AccessModifier, deps: &mut Vec<Dependency>, code: &str) {
+    if let Some(p) = node.child_by_field_name("parameters") {
+        for x in 0..p.child_count() {
+            if p.child(x).unwrap().kind() == "formal_parameter" {
+                add_typed_dependencies(p.child(x).unwrap(), DependencyKind::Parameter, access, deps, code);
+            }
+        }
+    }
+}
+
+fn add_typed_dependencies(node: Node, kind: DependencyKind, access: AccessModifier, deps: &mut Vec<Dependency>, code: &str) {
+    if let Some(t) = node.child_by_field_name("type") {
+        if let Ok(x) = resolve_field(node, vec!["declarator", "value", "type"]) {
+            deps.push(new_dependency(x, DependencyKind::Instantiation, access, code));
+        }
+        deps.push(new_dependency(t, kind, access, code));
+    }
+}
+
+/// Declared types of the variables visible in a method body, by name. Blocks
+/// are not told apart, a later declaration replacing an earlier one.
+type VariableTypes<'a> = HashMap<&'a str, Node<'a>>;
+
+/// Walks a method or constructor body, nested blocks and lambdas included, for
+/// its local variables and the objects created in its expression statements.
+/// Nested type declarations are left out as they get their own report.
+fn add_body_dependencies<'a>(
+    node: Node<'a>,
+    access: AccessModifier,
+    return_types: &HashMap<&'a str, Option<Node<'a>>>,
+    variables: &mut VariableTypes<'a>,
+    deps: &mut Vec<Dependency>,
+    code: &'a str,
+) {
+    match node.kind() {
+        "class_declaration" | "interface_declaration" | "enum_declaration" | "record_declaration" => return,
+        "local_variable_declaration" => {
+            add_local_variable_dependencies(node, access, return_types, variables, deps, code);
+            declare_variables(node, return_types, variables, code);
+        },
+        // try-with-resources, declared like a local variable with a single name
+        "resource" => {
+            add_resource_dependencies(node, access, return_types, variables, deps, code);
+            declare_variables(node, return_types, variables, code);
+        },
+        "enhanced_for_statement" => {
+            add_loop_variable_dependencies(node, access, return_types, variables, deps, code);
+            declare_variables(node, return_types, variables, code);
+        },
+        "expression_statement" => {
+            for i in 0..node.child_count() {
+                let expression_node = node.child(i).unwrap();
+                if expression_node.kind() == "method_invocation" {
+                    for j in 0..expression_node.child_count() {
+                        let obj_creation_node = expression_node.child(j).unwrap();
+                        if obj_creation_node.kind() == "object_creation_expression" {
+                            add_typed_dependencies(obj_creation_node, DependencyKind::Instantiation, access, deps, code);
                         }
                     }
                 }
-            },
-            _ => {}
+            }
+        },
+        _ => (),
+    }
+
+    for i in 0..node.named_child_count() {
+        add_body_dependencies(node.named_child(i).unwrap(), access, return_types, variables, deps, code);
+    }
+}
+
+/// Like `add_typed_dependencies`, except that a `var` declaration takes the type
+/// of its initializer, see `expression_type`. Nothing is reported when the type
+/// cannot be told.
+fn add_local_variable_dependencies<'a>(
+    node: Node<'a>,
+    access: AccessModifier,
+    return_types: &HashMap<&'a str, Option<Node<'a>>>,
+    variables: &VariableTypes<'a>,
+    deps: &mut Vec<Dependency>,
+    code: &'a str,
+) {
+    let Some(t) = node.child_by_field_name("type") else {
+        return;
+    };
+    if !is_var(t, code) {
+        return add_typed_dependencies(node, DependencyKind::LocalVariable, access, deps, code);
+    }
+    if let Ok(value) = resolve_field(node, vec!["declarator", "value"]) {
+        add_inferred_dependency(node, value, access, return_types, variables, deps, code);
+    }
+}
+
+fn add_resource_dependencies<'a>(
+    node: Node<'a>,
+    access: AccessModifier,
+    return_types: &HashMap<&'a str, Option<Node<'a>>>,
+    variables: &VariableTypes<'a>,
+    deps: &mut Vec<Dependency>,
+    code: &'a str,
+) {
+    let (Some(t), Some(value)) = (node.child_by_field_name("type"), node.child_by_field_name("value")) else {
+        return;
+    };
+    if !is_var(t, code) {
+        return add_typed_dependencies(node, DependencyKind::LocalVariable, access, deps, code);
+    }
+    add_inferred_dependency(node, value, access, return_types, variables, deps, code);
+}
+
+/// `var name = value`: reports the type of `value`, the object created by a
+/// `new` initializer being an instantiation as well.
+fn add_inferred_dependency<'a>(
+    declaration: Node<'a>,
+    value: Node<'a>,
+    access: AccessModifier,
+    return_types: &HashMap<&'a str, Option<Node<'a>>>,
+    variables: &VariableTypes<'a>,
+    deps: &mut Vec<Dependency>,
+    code: &'a str,
+) {
+    if value.kind() == "object_creation_expression" {
+        if let Some(created) = value.child_by_field_name("type") {
+            deps.push(new_dependency(created, DependencyKind::Instantiation, access, code));
+        }
+    }
+    if let Some(t) = expression_type(value, return_types, variables, code) {
+        add_declared_elsewhere(declaration, t, access, deps, code);
+    }
+}
+
+/// `for (Type x : xs)` reports `Type`, `for (var x : xs)` the element type of
+/// `xs` when it is an array or a generic collection whose type can be told.
+fn add_loop_variable_dependencies<'a>(
+    node: Node<'a>,
+    access: AccessModifier,
+    return_types: &HashMap<&'a str, Option<Node<'a>>>,
+    variables: &VariableTypes<'a>,
+    deps: &mut Vec<Dependency>,
+    code: &'a str,
+) {
+    if let Some(element) = loop_variable_type(node, return_types, variables, code) {
+        add_declared_elsewhere(node, element, access, deps, code);
+    }
+}
+
+/// Type of the variable of an enhanced for loop, the element type of the
+/// iterable for `var`.
+fn loop_variable_type<'a>(
+    node: Node<'a>,
+    return_types: &HashMap<&'a str, Option<Node<'a>>>,
+    variables: &VariableTypes<'a>,
+    code: &'a str,
+) -> Option<Node<'a>> {
+    let t = node.child_by_field_name("type")?;
+    match is_var(t, code) {
+        false => Some(t),
+        true => expression_type(node.child_by_field_name("value")?, return_types, variables, code).and_then(element_type),
+    }
+}
+
+/// Reports `t` as a local variable type, the edge coming from the declaration
+/// line as the type may be written elsewhere (e.g. a method return type).
+fn add_declared_elsewhere(declaration: Node, t: Node, access: AccessModifier, deps: &mut Vec<Dependency>, code: &str) {
+    let mut dep = new_dependency(t, DependencyKind::LocalVariable, access, code);
+    dep.line = declaration.start_position().row + 1;
+    deps.push(dep);
+}
+
+/// Type of an expression when the source tells it: the created or cast type,
+/// the return type of a method of the same class or the declared type of a
+/// variable.
+fn expression_type<'a>(
+    value: Node<'a>,
+    return_types: &HashMap<&'a str, Option<Node<'a>>>,
+    variables: &VariableTypes<'a>,
+    code: &'a str,
+) -> Option<Node<'a>> {
+    match value.kind() {
+        "object_creation_expression" | "cast_expression" | "array_creation_expression" => value.child_by_field_name("type"),
+        "method_invocation" => {
+            let own_method = match value.child_by_field_name("object") {
+                Some(object) => object.kind() == "this",
+                None => true,
+            };
+            let name = value.child_by_field_name("name").unwrap().utf8_text(code.as_bytes()).unwrap();
+            match return_types.get(name) {
+                Some(Some(t)) if own_method => Some(*t),
+                _ => None,
+            }
+        },
+        "identifier" => variables.get(value.utf8_text(code.as_bytes()).unwrap()).copied(),
+        "parenthesized_expression" => expression_type(value.named_child(0)?, return_types, variables, code),
+        _ => None,
+    }
+}
+
+/// Element type of an array or of a generic collection, its last type argument.
+fn element_type(t: Node) -> Option<Node> {
+    match t.kind() {
+        "array_type" => t.child_by_field_name("element"),
+        "generic_type" => {
+            let arguments = (0..t.named_child_count())
+                .map(|i| t.named_child(i).unwrap())
+                .find(|child| child.kind() == "type_arguments")?;
+            arguments.named_child(arguments.named_child_count().checked_sub(1)?)
+        },
+        _ => None,
+    }
+}
+
+fn is_var(t: Node, code: &str) -> bool {
+    t.utf8_text(code.as_bytes()).unwrap() == "var"
+}
+
+/// Declared types of the fields of the class body, by name.
+fn field_types<'a>(body: &Node<'a>, code: &'a str) -> VariableTypes<'a> {
+    let mut variables = HashMap::new();
+    for i in 0..body.named_child_count() {
+        let field = body.named_child(i).unwrap();
+        if field.kind() == "field_declaration" {
+            declare_variables(field, &HashMap::new(), &mut variables, code);
+        }
+    }
+    variables
+}
+
+fn declare_parameters<'a>(method: Node<'a>, variables: &mut VariableTypes<'a>, code: &'a str) {
+    if let Some(parameters) = method.child_by_field_name("parameters") {
+        for i in 0..parameters.named_child_count() {
+            declare_variables(parameters.named_child(i).unwrap(), &HashMap::new(), variables, code);
+        }
+    }
+}
+
+/// Records the variables introduced by a field, parameter, local, resource or
+/// enhanced for declaration, `var` ones with the type told by their initializer.
+fn declare_variables<'a>(
+    node: Node<'a>,
+    return_types: &HashMap<&'a str, Option<Node<'a>>>,
+    variables: &mut VariableTypes<'a>,
+    code: &'a str,
+) {
+    let Some(t) = node.child_by_field_name("type") else {
+        return;
+    };
+    let declared: Vec<(Option<Node>, Option<Node>)> = match node.kind() {
+        "enhanced_for_statement" => {
+            vec![(node.child_by_field_name("name"), loop_variable_type(node, return_types, variables, code))]
+        },
+        "resource" if is_var(t, code) => {
+            let inferred = node.child_by_field_name("value")
+                .and_then(|value| expression_type(value, return_types, variables, code));
+            vec![(node.child_by_field_name("name"), inferred)]
+        },
+        "field_declaration" | "local_variable_declaration" => (0..node.named_child_count())
+            .map(|i| node.named_child(i).unwrap())
+            .filter(|declarator| declarator.kind() == "variable_declarator")
+            .map(|declarator| {
+                let declared = match is_var(t, code) {
+                    false => Some(t),
+                    true => declarator.child_by_field_name("value")
+                        .and_then(|value| expression_type(value, return_types, variables, code)),
+                };
+                (declarator.child_by_field_name("name"), declared)
+            })
+            .collect(),
+        _ => vec![(node.child_by_field_name("name"), Some(t))],
+    };
+    for (name, t) in declared {
+        if let (Some(name), Some(t)) = (name, t) {
+            variables.insert(name.utf8_text(code.as_bytes()).unwrap(), t);
+        }
+    }
+}
+
+/// Return types of the methods declared in the class body, `None` for the
+/// overloaded names whose return types differ.
+fn method_return_types<'a>(body: &Node<'a>, code: &'a str) -> HashMap<&'a str, Option<Node<'a>>> {
+    let mut return_types: HashMap<&str, Option<Node>> = HashMap::new();
+    for i in 0..body.named_child_count() {
+        let method = body.named_child(i).unwrap();
+        if method.kind() != "method_declaration" {
+            continue;
         }
+        let (Some(name), Some(t)) = (method.child_by_field_name("name"), method.child_by_field_name("type")) else {
+            continue;
+        };
+        let name = name.utf8_text(code.as_bytes()).unwrap();
+        let text = t.utf8_text(code.as_bytes()).unwrap();
+        return_types.entry(name)
+            .and_modify(|known| {
+                if known.is_some_and(|k| k.utf8_text(code.as_bytes()).unwrap() != text) {
+                    *known = None;
+                }
+            })
+            .or_insert(Some(t));
     }
+    return_types
+}
 
-    deps.sort();
-    deps.dedup();
-    deps
+fn new_dependency(type_node: Node, kind: DependencyKind, access: AccessModifier, code: &str) -> Dependency {
+    Dependency {
+        target: type_node.utf8_text(code.as_bytes()).unwrap().to_string(),
+        kind,
+        access: Some(access),
+        target_kind: None,
+        line: type_node.start_position().row + 1,
+        snippet: None,
+    }
 }
 
-fn get_and_add_dep_from_child_name(node: Node, child_name: String, mut deps: Vec<String>, code: &str) -> Vec<String> {
-    if let Some(t) = node.child_by_field_name(child_name)
-    {
-        match resolve_field(node, vec!["declarator", "value", "type"]) {
-            Ok(x) => deps.push(x.utf8_text(code.as_bytes()).unwrap().to_string()),
-            Err(_) => (),
+fn has_modifier(node: &Node, keyword: &str) -> bool {
+    for i in 0..node.named_child_count() {
+        let child = node.named_child(i).unwrap();
+        if child.kind() == "modifiers" {
+            for j in 0..child.child_count() {
+                if child.child(j).unwrap().kind() == keyword {
+                    return true;
+                }
+            }
         }
-        deps.push(t.utf8_text(code.as_bytes()).unwrap().to_string());
     }
-    deps
+    false
+}
+
+fn access_modifier(node: &Node, default: AccessModifier) -> AccessModifier {
+    if has_modifier(node, "public") {
+        AccessModifier::Public
+    } else if has_modifier(node, "protected") {
+        AccessModifier::Protected
+    } else if has_modifier(node, "private") {
+        AccessModifier::Private
+    } else {
+        default
+    }
 }
 
-fn filter_dependencies(dependencies: Vec<String>) -> Vec<String> {
+fn filter_dependencies(dependencies: Vec<Dependency>) -> Vec<Dependency> {
     let prims = [
         "byte", "short", "int", "long",
         "float", "double", "boolean", "char",
@@ -208,12 +904,63 @@ fn filter_dependencies(dependencies: Vec<String>) -> Vec<String> {
     ];
 
     dependencies.into_iter()
-        .filter(|ty| !prims.contains(&ty.as_str()))
+        .filter(|dep| !prims.contains(&dep.target.as_str()))
         .collect()
 }
 
+/// Imports keep their declaration order, the other dependencies are sorted.
+fn dependency_names(edges: &[Dependency]) -> Vec<String> {
+    let imports = edges.iter()
+        .filter(|dep| dep.kind == DependencyKind::Import)
+        .map(|dep| dep.target.clone());
+    let mut others: Vec<String> = edges.iter()
+        .filter(|dep| dep.kind != DependencyKind::Import)
+        .map(|dep| dep.target.clone())
+        .collect();
+    others.sort();
+    others.dedup();
+    imports.chain(others).collect()
+}
+
+fn apply_suppressions(classes: &mut [ClassDepsReport], suppressions: &Suppressions, package: &str, imports: &[String]) {
+    for class in classes {
+        class.edges.retain(|dep| !suppressions.is_suppressed(&dep.target, package, imports));
+        class.class_deps = dependency_names(&class.edges);
+        for method in class.methods.iter_mut() {
+            method.deps.retain(|dep| !suppressions.is_suppressed(&dep.target, package, imports));
+        }
+        class.calls.retain(|call| match &call.receiver {
+            Some(receiver) => !suppressions.is_suppressed(receiver, package, imports),
+            None => true,
+        });
+        apply_suppressions(&mut class.nested_classes, suppressions, package, imports);
+    }
+}
+
+/// Sets `target_kind` on every edge pointing to one of `classes` (nested ones included).
+pub fn annotate_internal_targets(classes: &mut [ClassDepsReport]) {
+    fn apply(classes: &mut [ClassDepsReport], kinds: &mut impl Iterator<Item = Vec<Option<TypeKind>>>) {
+        for class in classes {
+            let class_kinds = kinds.next().unwrap_or_default();
+            for (edge, kind) in class.edges.iter_mut().zip(class_kinds) {
+                edge.target_kind = kind;
+            }
+            apply(&mut class.nested_classes, kinds);
+        }
+    }
+
+    let index = ProjectIndex::new(classes);
+    // resolved up front in the same pre-order `apply` walks the classes
+    let kinds: Vec<Vec<Option<TypeKind>>> = flatten_classes(classes).into_iter()
+        .map(|(class, top_level)| class.edges.iter()
+            .map(|edge| index.kind_of(&index.resolve(class, top_level, &edge.target)))
+            .collect())
+        .collect();
+    apply(classes, &mut kinds.into_iter());
+}
+
 fn resolve_field<'a>(node: Node<'a>, fields: Vec<&'a str>) -> Result<Node<'a>, String> {
-    let mut return_node: Node = node.clone();
+    let mut return_node: Node = node;
     for f in fields {
         if let Some(n) = return_node.child_by_field_name(f) {
             return_node = n;
@@ -225,56 +972,431 @@ fn resolve_field<'a>(node: Node<'a>, fields: Vec<&'a str>) -> Result<Node<'a>, S
 }
 
 pub async fn get_package_dependencies(package_folder: String) -> Result<PackageDepsReport, String> {
+    get_package_dependencies_with_options(package_folder, &AnalysisOptions::default()).await
+}
+
+/// Analyses the files of the package concurrently, at most `options.concurrency`
+/// at a time. Files failing the analysis end up in the report diagnostics.
+///
+/// With `options.recursive` every sub-folder is analysed as well and the result
+/// is a tree of reports mirroring the package hierarchy, the folders that could
+/// not be walked ending up in the diagnostics of the root one.
+pub async fn get_package_dependencies_with_options(
+    package_folder: String,
+    options: &AnalysisOptions,
+) -> Result<PackageDepsReport, String> {
+    if !options.recursive {
+        return analyse_package_folder(package_folder, options).await;
+    }
+
+    let root = PathBuf::from(&package_folder);
+    let excluded = options.exclude_set()?;
+    // sub-folders that cannot be walked are reported on the root package
+    let mut walk_diagnostics: Vec<FileDiagnostic> = Vec::new();
+    let folders: Vec<PathBuf> = WalkDir::new(&root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(|e| e.path() == root || !is_excluded(&excluded, &root, e.path()))
+        .filter_map(|e| match e {
+            Ok(entry) => Some(entry),
+            Err(e) => {
+                let file = e.path().unwrap_or(&root).to_string_lossy().to_string();
+                walk_diagnostics.push(FileDiagnostic { file, message: e.to_string() });
+                None
+            }
+        })
+        .filter(|e| e.file_type().is_dir())
+        .map(|e| e.into_path())
+        .collect();
+
+    let mut reports: HashMap<PathBuf, PackageDepsReport> = HashMap::new();
+    for folder in &folders {
+        let report = analyse_package_folder(folder.to_string_lossy().to_string(), options).await?;
+        reports.insert(folder.clone(), report);
+    }
+
+    // deepest folders first, so every report is complete when moved into its parent
+    for folder in folders.iter().rev() {
+        if folder == &root {
+            continue;
+        }
+        let report = reports.remove(folder).unwrap();
+        if let Some(parent) = folder.parent().and_then(|parent| reports.get_mut(parent)) {
+            parent.sub_packages.insert(0, report);
+        }
+    }
+
+    let mut report = reports.remove(&root).ok_or(String::from("Invalid folder"))?;
+    report.diagnostics.append(&mut walk_diagnostics);
+    report.diagnostics.sort();
+    Ok(report)
+}
+
+async fn analyse_package_folder(
+    package_folder: String,
+    options: &AnalysisOptions,
+) -> Result<PackageDepsReport, String> {
     let paths = match read_dir(package_folder.clone()) {
         Ok(p) => p,
         _ => return Err(String::from("Invalid folder"))
     };
 
-    let p_folder = package_folder.clone();
-    let mut dependencies: Vec<String> = Vec::new();
+    let excluded = options.exclude_set()?;
+    let limit = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let mut diagnostics: Vec<FileDiagnostic> = Vec::new();
+    let mut tasks = JoinSet::new();
     for path in paths {
-        let file_name = path.unwrap().file_name().into_string().unwrap();
-        if file_name.contains(".java") {
-            let file = format!("{p_folder}/{file_name}");
-            match get_class_dependencies(file).await {
-                Ok(classes) => {
-                    for mut class in classes {
-                        dependencies.append(&mut class.class_deps);
-                    }
-                }
-                Err(e) => println!("Err in getting package deps: {} for file {}", e, file_name),
+        let path = match path {
+            Ok(entry) => entry.path(),
+            Err(e) => {
+                diagnostics.push(FileDiagnostic { file: package_folder.clone(), message: e.to_string() });
+                continue;
             }
+        };
+        if !path.is_file() || !options.is_java_source(&path) || is_excluded(&excluded, Path::new(&package_folder), &path) {
+            continue;
         }
+
+        let file = path.to_string_lossy().to_string();
+        let options = options.clone();
+        let limit = limit.clone();
+        tasks.spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            let result = get_class_dependencies_with_options(file.clone(), &options).await;
+            (file, result)
+        });
     }
 
-    dependencies.sort();
-    dependencies.dedup();
+    let mut dependencies: BTreeSet<String> = BTreeSet::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((_, Ok(classes))) => {
+                for class in classes {
+                    dependencies.extend(class.get_dependencies());
+                }
+            },
+            Ok((file, Err(message))) => diagnostics.push(FileDiagnostic { file, message }),
+            Err(e) => diagnostics.push(FileDiagnostic {
+                file: package_folder.clone(),
+                message: format!("Analysis task failed: {}", e),
+            }),
+        }
+    }
+    diagnostics.sort();
 
     Ok(PackageDepsReport {
         package_name: package_folder,
-        package_deps: dependencies
+        package_deps: dependencies.into_iter().collect(),
+        diagnostics,
+        sub_packages: Vec::new(),
     })
 }
 
 pub async fn get_project_dependencies(project_folder: String) -> Result<ProjectDepsReport, String> {
-    let mut dependencies: Vec<String> = Vec::new();
-    for entry in WalkDir::new(project_folder.clone()).into_iter().filter_map(|e| e.ok()) {
-        let file_name = entry.path().file_name().unwrap().to_str().unwrap();
-        if entry.path().is_file() && file_name.contains(".java") {
-            match get_class_dependencies(entry.path().to_str().unwrap().to_string()).await {
-                Ok(vector) => for c in vector {
-                    dependencies.append(&mut c.get_dependencies());
-                },
-                Err(e) => return Err(e)
+    get_multi_root_project_dependencies(vec![project_folder]).await
+}
+
+/// Analyses several source roots at once (e.g. `core/src/main/java` and
+/// `api/src/main/java`), merging them in a single report.
+pub async fn get_multi_root_project_dependencies(project_folders: Vec<String>) -> Result<ProjectDepsReport, String> {
+    get_project_dependencies_with_options(project_folders, &AnalysisOptions::default()).await
+}
+
+/// Project analysis keeping only the aggregated dependencies, unless
+/// `options.retain_classes` asks for every class report as well.
+pub async fn get_project_dependencies_with_options(
+    project_folders: Vec<String>,
+    options: &AnalysisOptions,
+) -> Result<ProjectDepsReport, String> {
+    if !options.retain_classes {
+        return stream_project_dependencies(project_folders, options, &mut DiscardSink).await;
+    }
+
+    let mut classes: Vec<ClassDepsReport> = Vec::new();
+    let mut report = stream_project_dependencies(project_folders, options, &mut classes).await?;
+    annotate_internal_targets(&mut classes);
+    report.classes = classes;
+    report.classes_retained = true;
+    Ok(report)
+}
+
+/// Brings `previous` up to date after `changed_files` were modified, created or
+/// deleted: only those files are analysed again, the classes of deleted ones are
+/// dropped and the aggregated dependencies are recomputed. Paths must be spelled
+/// as the walker found them, i.e. starting with one of the project roots, and
+/// `previous` must come from an analysis retaining its classes: a streamed
+/// report is refused, as its dependencies could not be recomputed.
+pub async fn update_project_report(
+    previous: ProjectDepsReport,
+    changed_files: &[PathBuf],
+) -> Result<ProjectDepsReport, String> {
+    update_project_report_with_options(previous, changed_files, &AnalysisOptions::default()).await
+}
+
+pub async fn update_project_report_with_options(
+    previous: ProjectDepsReport,
+    changed_files: &[PathBuf],
+    options: &AnalysisOptions,
+) -> Result<ProjectDepsReport, String> {
+    if !previous.classes_retained {
+        return Err(String::from(
+            "Failed to update the project report: it kept no classes, analyse the project with retain_classes",
+        ));
+    }
+    let mut report = previous;
+    let excluded = options.exclude_set()?;
+
+    report.classes.retain(|class| !changed_files.iter().any(|file| Path::new(&class.source_file) == file));
+    report.diagnostics.retain(|diagnostic| !changed_files.iter().any(|file| Path::new(&diagnostic.file) == file));
+    for file in changed_files {
+        let Some(root) = report.project_folders.iter().find(|root| file.starts_with(root.as_str())) else {
+            continue;
+        };
+        if !file.is_file() || !options.is_java_source(file) || is_excluded(&excluded, Path::new(root), file) {
+            continue;
+        }
+        if let Some(diagnostic) = oversized_file(file, options) {
+            report.diagnostics.push(diagnostic);
+            continue;
+        }
+
+        let file = file.to_string_lossy().to_string();
+        let mut classes = match get_class_dependencies_with_options(file.clone(), options).await {
+            Ok(classes) => classes,
+            Err(message) => {
+                report.diagnostics.push(FileDiagnostic { file, message });
+                continue;
             }
+        };
+        for class in classes.iter_mut() {
+            class.label_root(root);
         }
+        report.classes.append(&mut classes);
     }
 
-    dependencies.sort();
-    dependencies.dedup();
+    annotate_internal_targets(&mut report.classes);
+    let dependencies: BTreeSet<String> = report.classes.iter()
+        .flat_map(|class| class.get_dependencies())
+        .collect();
+    report.project_deps = dependencies.into_iter().collect();
+    Ok(report)
+}
+
+/// Diagnostic for a file above `options.max_file_size`, which is skipped rather
+/// than failing the whole walk.
+fn oversized_file(path: &Path, options: &AnalysisOptions) -> Option<FileDiagnostic> {
+    let limit = options.max_file_size?;
+    let size = path.metadata().ok()?.len();
+    (size > limit).then(|| FileDiagnostic {
+        file: path.to_string_lossy().to_string(),
+        message: format!("Skipped, the file is {} bytes, above the {} bytes limit", size, limit),
+    })
+}
+
+/// Walks the project roots handing each file's reports to `sink` right after the
+/// file is analysed; only the aggregated dependency names are kept in memory.
+/// Files failing the analysis end up in the report diagnostics.
+/// Since no project-wide index is built, `target_kind` is only set for targets
+/// declared in the same file.
+pub async fn stream_project_dependencies(
+    project_folders: Vec<String>,
+    options: &AnalysisOptions,
+    sink: &mut dyn ReportSink,
+) -> Result<ProjectDepsReport, String> {
+    if let Some(missing) = project_folders.iter().find(|folder| !Path::new(folder).is_dir()) {
+        return Err(format!("Failed to read project folder {}: not a directory", missing));
+    }
+
+    let excluded = options.exclude_set()?;
+    let mut dependencies: BTreeSet<String> = BTreeSet::new();
+    let mut diagnostics: Vec<FileDiagnostic> = Vec::new();
+    for project_folder in &project_folders {
+        for entry in WalkDir::new(project_folder) {
+            let entry = match entry {
+                Ok(entry) => entry,
+                Err(e) => {
+                    let file = e.path().unwrap_or(Path::new(project_folder)).to_string_lossy().to_string();
+                    diagnostics.push(FileDiagnostic { file, message: e.to_string() });
+                    continue;
+                }
+            };
+            if is_excluded(&excluded, Path::new(project_folder), entry.path()) {
+                continue;
+            }
+            if entry.path().is_file() && options.is_java_source(entry.path()) {
+                if let Some(diagnostic) = oversized_file(entry.path(), options) {
+                    diagnostics.push(diagnostic);
+                    continue;
+                }
+                let file = entry.path().to_string_lossy().to_string();
+                let mut classes = match get_class_dependencies_with_options(file.clone(), options).await {
+                    Ok(classes) => classes,
+                    Err(message) => {
+                        diagnostics.push(FileDiagnostic { file, message });
+                        continue;
+                    }
+                };
+                for c in classes.iter_mut() {
+                    c.label_root(project_folder);
+                    dependencies.extend(c.get_dependencies());
+                }
+                sink.accept(entry.path(), classes)?;
+            }
+        }
+    }
+    sink.finish()?;
 
     Ok(ProjectDepsReport {
-        project_folder,
-        project_deps: dependencies
+        project_folders,
+        project_deps: dependencies.into_iter().collect(),
+        classes: Vec::new(),
+        classes_retained: false,
+        diagnostics,
     })
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyser::graph::ClassGraph;
+    use crate::common::format::plain_lines;
+
+    fn analyse(code: &str) -> Vec<ClassDepsReport> {
+        analyse_source(code, "Test.java", &AnalysisOptions::default())
+    }
+
+    #[test]
+    fn nested_classes_are_named_after_their_enclosing_chain() {
+        let classes = analyse("package a; class Outer { static class Mid { static class In { Mid m; } } }");
+        let names: Vec<String> = flatten_classes(&classes).iter().map(|(class, _)| class.qualified_name()).collect();
+        assert_eq!(names, ["a.Outer", "a.Outer.Mid", "a.Outer.Mid.In"]);
+        assert_eq!(plain_lines(&classes), ["a.Outer.Mid.In -> Mid [field]"]);
+    }
+
+    #[test]
+    fn nested_targets_resolve_through_the_enclosing_scope() {
+        let classes = analyse("package a; class Outer { Mid m; static class Mid { In i; static class In {} } }");
+        let graph = ClassGraph::build(&classes);
+        assert!(graph.edges.contains_key(&(String::from("a.Outer"), String::from("a.Outer.Mid"))));
+        assert!(graph.edges.contains_key(&(String::from("a.Outer.Mid"), String::from("a.Outer.Mid.In"))));
+    }
+
+    #[test]
+    fn literals_and_comments_create_no_dependency() {
+        let classes = analyse(
+            r#"package a;
+class Lit {
+    void run() throws Exception {
+        Object s = "com.foo.Bar"; // uses Class.forName("com.foo.Bar")
+        /* Class.forName("com.foo.Baz");
+           Baz.class */
+        Object e = "\"Class.forName(\"com.foo.Esc\")\"";
+        char c = '"';
+        Object t = """
+            Class.forName("com.foo.Text");
+            Text.class
+            """;
+        Class.forName("com.foo.Real");
+    }
+}"#,
+        );
+        assert_eq!(plain_lines(&classes), ["a.Lit -> Object [local]", "a.Lit -> com.foo.Real [reflective]"]);
+    }
+
+    fn has_local(classes: &[ClassDepsReport], line: &str) -> bool {
+        plain_lines(classes).iter().any(|l| l == line)
+    }
+
+    #[test]
+    fn var_is_inferred_in_nested_blocks() {
+        let classes = analyse(
+            "package a; class Shop { void run(boolean b) { if (b) { var i = new Item(); } else { while (b) { var o = (Order) null; } } } }",
+        );
+        assert!(has_local(&classes, "a.Shop -> Item [local]"));
+        assert!(has_local(&classes, "a.Shop -> Item [new]"));
+        assert!(has_local(&classes, "a.Shop -> Order [local]"));
+    }
+
+    #[test]
+    fn var_is_inferred_in_for_and_try_statements() {
+        let classes = analyse(
+            "package a; class Shop { Stock stock() { return null; } void run() { \
+            for (var i = new Item(); ; ) { } \
+            try (var r = new Reader()) { var s = stock(); } catch (Exception e) { } } }",
+        );
+        assert!(has_local(&classes, "a.Shop -> Item [local]"));
+        assert!(has_local(&classes, "a.Shop -> Reader [local]"));
+        assert!(has_local(&classes, "a.Shop -> Stock [local]"));
+    }
+
+    #[test]
+    fn var_loop_variable_takes_the_element_type() {
+        let classes = analyse(
+            "package a; import java.util.List; class Shop { List<Item> items; \
+            void run(Order[] orders) { for (var i : items) { } for (var o : orders) { } for (var l : lines()) { } } \
+            Map<String, Line> lines() { return null; } }",
+        );
+        assert!(has_local(&classes, "a.Shop -> Item [local]"));
+        assert!(has_local(&classes, "a.Shop -> Order [local]"));
+        assert!(has_local(&classes, "a.Shop -> Line [local]"));
+    }
+
+    #[test]
+    fn var_is_inferred_in_lambdas() {
+        let classes = analyse("package a; class Shop { void run() { Runnable r = () -> { var i = new Item(); }; } }");
+        assert!(has_local(&classes, "a.Shop -> Item [local]"));
+    }
+
+    #[test]
+    fn var_is_inferred_in_constructors() {
+        let classes = analyse("package a; class Shop { Shop(Stock stock) { var s = stock; var i = new Item(); } }");
+        assert!(has_local(&classes, "a.Shop -> Stock [local]"));
+        assert!(has_local(&classes, "a.Shop -> Item [local]"));
+    }
+
+    #[tokio::test]
+    async fn updating_a_streamed_report_fails() {
+        let root = std::env::temp_dir().join(format!("streamed-report-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        // no dependency at all, a streamed report looks like a retained one of an empty project
+        std::fs::write(root.join("Shop.java"), "package a; class Shop { }").unwrap();
+
+        let streamed = get_project_dependencies(root.to_string_lossy().to_string()).await.unwrap();
+        let updated = update_project_report(streamed, &[root.join("Shop.java")]).await;
+        let options = AnalysisOptions { retain_classes: true, ..AnalysisOptions::default() };
+        let retained = get_project_dependencies_with_options(vec![root.to_string_lossy().to_string()], &options).await.unwrap();
+        let refreshed = update_project_report(retained, &[root.join("Shop.java")]).await;
+        std::fs::remove_dir_all(&root).unwrap();
+        assert!(updated.is_err());
+        assert_eq!(refreshed.unwrap().classes.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn unreadable_files_become_diagnostics() {
+        let root = std::env::temp_dir().join(format!("unreadable-file-{}", std::process::id()));
+        std::fs::create_dir_all(&root).unwrap();
+        std::fs::write(root.join("Shop.java"), "package a; class Shop { Item item; }").unwrap();
+        std::fs::write(root.join("Bad.java"), b"package a; class Bad { \xff }").unwrap();
+
+        let report = get_project_dependencies(root.to_string_lossy().to_string()).await;
+        std::fs::remove_dir_all(&root).unwrap();
+        let report = report.unwrap();
+        assert_eq!(report.project_deps, ["Item"]);
+        assert_eq!(report.diagnostics.len(), 1);
+        assert!(report.diagnostics[0].file.ends_with("Bad.java"));
+    }
+
+    #[test]
+    fn var_receivers_reach_the_call_graph() {
+        let code = "package a; class Shop { Repo make() { return null; } List<Line> lines; \
+            void run() { var r = new Repo(); r.save(); var m = make(); m.load(); for (var l : lines) { l.print(); } } }";
+        let options = AnalysisOptions { call_graph: true, ..AnalysisOptions::default() };
+        let classes = analyse_source(code, "Test.java", &options);
+        let calls: Vec<(Option<&str>, &str)> = classes[0].calls.iter()
+            .map(|call| (call.receiver.as_deref(), call.method.as_str()))
+            .collect();
+        assert!(calls.contains(&(Some("Repo"), "save")));
+        assert!(calls.contains(&(Some("Repo"), "load")));
+        assert!(calls.contains(&(Some("Line"), "print")));
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::{BTreeMap, BTreeSet};
+use crate::analyser::index::{flatten_classes, package_of, ProjectIndex};
+use crate::common::types::{ClassDepsReport, DependencyKind};
+
+/// Class level dependency graph of a project, every target resolved to its
+/// qualified name when possible.
+#[derive(Debug, Default)]
+pub struct ClassGraph {
+    /// Project classes and the package they belong to
+    pub classes: BTreeMap<String, String>,
+    /// Edges between qualified names, with the kinds of use behind each of them
+    pub edges: BTreeMap<(String, String), BTreeSet<DependencyKind>>,
+}
+
+impl ClassGraph {
+    pub fn build(classes: &[ClassDepsReport]) -> ClassGraph {
+        let index = ProjectIndex::new(classes);
+        let mut graph = ClassGraph::default();
+        for (class, top_level) in flatten_classes(classes) {
+            let from = class.qualified_name();
+            graph.classes.insert(from.clone(), class.package_name.clone());
+            for edge in &class.edges {
+                let to = index.resolve(class, top_level, &edge.target);
+                // wildcard imports name a package, not a type
+                if to.ends_with(".*") || to == from {
+                    continue;
+                }
+                graph.edges.entry((from.clone(), to)).or_default().insert(edge.kind);
+            }
+        }
+        graph
+    }
+
+    pub fn is_project_class(&self, name: &str) -> bool {
+        self.classes.contains_key(name)
+    }
+
+    /// Package of a node, for types outside the project guessed from the name.
+    pub fn package_of<'a>(&'a self, name: &'a str) -> &'a str {
+        match self.classes.get(name) {
+            Some(package) => package,
+            None => package_of(name),
+        }
+    }
+
+    pub fn packages(&self) -> BTreeSet<&str> {
+        self.classes.values().map(|package| package.as_str()).collect()
+    }
+
+    /// The classes of `package` with their incoming and outgoing edges, the other
+    /// end of which may lie in another package.
+    pub fn package_partition(&self, package: &str) -> ClassGraph {
+        let mut partition = ClassGraph::default();
+        for (name, class_package) in &self.classes {
+            if class_package == package {
+                partition.classes.insert(name.clone(), class_package.clone());
+            }
+        }
+        for ((from, to), kinds) in &self.edges {
+            if partition.classes.contains_key(from) || partition.classes.contains_key(to) {
+                partition.edges.insert((from.clone(), to.clone()), kinds.clone());
+            }
+        }
+        partition
+    }
+}
@@ -0,0 +1,114 @@
+use std::collections::HashMap;
+use crate::common::types::{ClassDepsReport, TypeKind};
+
+/// Lookup table of the types declared in the analysed sources, used to turn the
+/// names written in the code into qualified names.
+pub struct ProjectIndex {
+    kinds: HashMap<String, TypeKind>,
+    by_simple_name: HashMap<String, Vec<String>>,
+}
+
+impl ProjectIndex {
+    pub fn new(classes: &[ClassDepsReport]) -> Self {
+        let mut index = ProjectIndex { kinds: HashMap::new(), by_simple_name: HashMap::new() };
+        for (class, _) in flatten_classes(classes) {
+            let qualified = class.qualified_name();
+            if index.kinds.insert(qualified.clone(), class.type_kind).is_none() {
+                index.by_simple_name.entry(class.class_name.clone()).or_default().push(qualified);
+            }
+        }
+        index
+    }
+
+    pub fn contains(&self, qualified_name: &str) -> bool {
+        self.kinds.contains_key(qualified_name)
+    }
+
+    pub fn kind_of(&self, qualified_name: &str) -> Option<TypeKind> {
+        self.kinds.get(qualified_name).copied()
+    }
+
+    /// Qualified name of `target` as seen from `class`, `top_level` being the
+    /// outermost class of the same file (it carries the imports). Bare names not
+    /// matching an import nor a project type are returned as they are.
+    pub fn resolve(&self, class: &ClassDepsReport, top_level: &ClassDepsReport, target: &str) -> String {
+        let name = type_key(target);
+        if name.contains('.') {
+            // `Outer.Inner` written from the same package
+            let same_package = qualify(&class.package_name, name);
+            return match self.contains(&same_package) {
+                true => same_package,
+                false => name.to_string(),
+            };
+        }
+
+        // nested in the class itself or in one of the classes enclosing it, innermost first
+        let mut scope: Vec<&str> = class.enclosing_classes.iter().map(String::as_str).collect();
+        scope.push(&class.class_name);
+        while !scope.is_empty() {
+            let candidate = qualify(&class.package_name, &format!("{}.{}", scope.join("."), name));
+            if self.contains(&candidate) {
+                return candidate;
+            }
+            scope.pop();
+        }
+
+        let imports = top_level.imports();
+        if let Some(import) = imports.iter().find(|import| import.rsplit('.').next() == Some(name)) {
+            return import.to_string();
+        }
+
+        let same_package = qualify(&class.package_name, name);
+        if self.contains(&same_package) {
+            return same_package;
+        }
+
+        for import in &imports {
+            if let Some(package) = import.strip_suffix(".*") {
+                let candidate = qualify(package, name);
+                if self.contains(&candidate) {
+                    return candidate;
+                }
+            }
+        }
+
+        match self.by_simple_name.get(name) {
+            Some(candidates) if candidates.len() == 1 => candidates[0].clone(),
+            _ => name.to_string(),
+        }
+    }
+}
+
+/// Every class, nested ones included, paired with the top-level class of its file.
+pub fn flatten_classes(classes: &[ClassDepsReport]) -> Vec<(&ClassDepsReport, &ClassDepsReport)> {
+    fn visit<'a>(class: &'a ClassDepsReport, top_level: &'a ClassDepsReport, out: &mut Vec<(&'a ClassDepsReport, &'a ClassDepsReport)>) {
+        out.push((class, top_level));
+        for nested in &class.nested_classes {
+            visit(nested, top_level, out);
+        }
+    }
+
+    let mut out = Vec::new();
+    for class in classes {
+        visit(class, class, &mut out);
+    }
+    out
+}
+
+/// Strips generics and array markers, `List<Foo>[]` becomes `List`.
+pub fn type_key(target: &str) -> &str {
+    target.split('<').next().unwrap_or(target).trim_end_matches("[]").trim()
+}
+
+/// Package of a qualified name, empty for bare names.
+pub fn package_of(qualified_name: &str) -> &str {
+    qualified_name.rsplit_once('.').map(|(package, _)| package).unwrap_or("")
+}
+
+fn qualify(package: &str, name: &str) -> String {
+    if package.is_empty() {
+        name.to_string()
+    } else {
+        format!("{package}.{name}")
+    }
+}
@@ -0,0 +1,104 @@
+use std::path::Path;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+
+/// Files above 10 MiB are generated or data rather than hand written sources.
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 10 * 1024 * 1024;
+
+/// Tuning knobs shared by the analysis entry points.
+#[derive(Debug, Clone)]
+pub struct AnalysisOptions {
+    /// Files bigger than this many bytes are not loaded: package and project
+    /// analysis skip them with a diagnostic.
+    pub max_file_size: Option<u64>,
+    /// Globs, relative to the walked root, of the files to skip.
+    pub exclude: Vec<String>,
+    /// Packages (e.g. `java`, `javax`) whose types are not reported.
+    pub stdlib_prefixes: Vec<String>,
+    /// How many files of a package are analysed at the same time.
+    pub concurrency: usize,
+    /// Whether package analysis descends into the sub-packages.
+    pub recursive: bool,
+    /// Extensions besides `java` of the files to analyse (e.g. `jav`).
+    pub extra_extensions: Vec<String>,
+    /// Whether class reports also break dependencies down per method.
+    pub per_method: bool,
+    /// Whether method invocations are recorded to build the call graph.
+    pub call_graph: bool,
+    /// Whether every edge keeps the source line it comes from.
+    pub snippets: bool,
+    /// Whether project analysis keeps every class report, labelled with its
+    /// root, besides the aggregated dependencies.
+    pub retain_classes: bool,
+}
+
+impl Default for AnalysisOptions {
+    fn default() -> Self {
+        Self {
+            max_file_size: Some(DEFAULT_MAX_FILE_SIZE),
+            exclude: Vec::new(),
+            stdlib_prefixes: Vec::new(),
+            concurrency: 8,
+            recursive: false,
+            extra_extensions: Vec::new(),
+            per_method: false,
+            call_graph: false,
+            snippets: false,
+            retain_classes: false,
+        }
+    }
+}
+
+impl AnalysisOptions {
+    /// Whether `path` names a Java source, by extension. Hidden files are left out
+    /// as they are editor leftovers such as `.#Foo.java` locks.
+    pub fn is_java_source(&self, path: &Path) -> bool {
+        let hidden = path.file_name()
+            .and_then(|name| name.to_str())
+            .is_none_or(|name| name.starts_with('.'));
+        if hidden {
+            return false;
+        }
+
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("java") => true,
+            Some(ext) => self.extra_extensions.iter().any(|extra| extra.trim_start_matches('.') == ext),
+            None => false,
+        }
+    }
+
+    pub fn exclude_set(&self) -> Result<GlobSet, String> {
+        let mut builder = GlobSetBuilder::new();
+        for pattern in &self.exclude {
+            match Glob::new(pattern) {
+                Ok(glob) => builder.add(glob),
+                Err(e) => return Err(format!("Invalid exclude pattern {}: {}", pattern, e)),
+            };
+        }
+        builder.build().map_err(|e| format!("Invalid exclude patterns: {}", e))
+    }
+}
+
+/// Whether `path`, found while walking `root`, matches one of the exclude globs.
+pub fn is_excluded(excluded: &GlobSet, root: &Path, path: &Path) -> bool {
+    excluded.is_match(path.strip_prefix(root).unwrap_or(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn java_sources_are_told_by_their_last_extension() {
+        let options = AnalysisOptions::default();
+        assert!(options.is_java_source(Path::new("src/Foo.java")));
+        assert!(!options.is_java_source(Path::new("src/Foo.java.bak")));
+        assert!(!options.is_java_source(Path::new("src/NotJava.javascript")));
+        assert!(!options.is_java_source(Path::new("src/.#Foo.java")));
+        assert!(!options.is_java_source(Path::new("src/.Foo.java.swp")));
+        assert!(!options.is_java_source(Path::new("src/Foo.jav")));
+
+        let options = AnalysisOptions { extra_extensions: vec![String::from(".jav")], ..AnalysisOptions::default() };
+        assert!(options.is_java_source(Path::new("src/Foo.jav")));
+        assert!(!options.is_java_source(Path::new("src/.#Foo.jav")));
+    }
+}
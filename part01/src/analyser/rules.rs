@@ -0,0 +1,175 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use crate::analyser::config::{EdgeRule, Layer, ProjectConfig, Thresholds};
+use crate::analyser::index::{flatten_classes, package_of, ProjectIndex};
+use crate::common::types::ClassDepsReport;
+
+/// A project rule broken by a class.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub struct RuleViolation {
+    pub class: String,
+    pub message: String,
+    pub file: String,
+    /// Line of the offending edge, `None` for rules about the class as a whole
+    pub line: Option<usize>,
+}
+
+impl Display for RuleViolation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}: {}", self.file, line, self.class, self.message),
+            None => write!(f, "{}: {}: {}", self.file, self.class, self.message),
+        }
+    }
+}
+
+/// Runs every rule configured for the project, suppressed edges never reach this point.
+pub fn check_rules(classes: &[ClassDepsReport], config: &ProjectConfig) -> Vec<RuleViolation> {
+    let mut violations = check_layers(classes, &config.layers);
+    violations.append(&mut check_edge_rules(classes, &config.edge_rules));
+    violations.append(&mut check_thresholds(classes, &config.thresholds));
+    violations
+}
+
+/// Layers are listed from the top down: a class may use types of its own layer
+/// and of the ones below, never of a layer above.
+pub fn check_layers(classes: &[ClassDepsReport], layers: &[Layer]) -> Vec<RuleViolation> {
+    if layers.is_empty() {
+        return Vec::new();
+    }
+
+    let index = ProjectIndex::new(classes);
+    let mut violations = BTreeSet::new();
+    for (class, top_level) in flatten_classes(classes) {
+        let Some(class_layer) = layer_of(layers, &class.package_name) else {
+            continue;
+        };
+        for edge in &class.edges {
+            let target = index.resolve(class, top_level, &edge.target);
+            match layer_of(layers, package_of(&target)) {
+                Some(target_layer) if target_layer < class_layer => {
+                    violations.insert(RuleViolation {
+                        class: class.qualified_name(),
+                        message: format!(
+                            "layer {} must not depend on {} of upper layer {}",
+                            layers[class_layer].name, target, layers[target_layer].name
+                        ),
+                        file: class.source_file.clone(),
+                        line: Some(edge.line),
+                    });
+                },
+                _ => (),
+            }
+        }
+    }
+    violations.into_iter().collect()
+}
+
+/// An edge breaks the rules when a forbidding rule matches it and no allowing
+/// one does.
+pub fn check_edge_rules(classes: &[ClassDepsReport], rules: &[EdgeRule]) -> Vec<RuleViolation> {
+    if !rules.iter().any(|rule| !rule.allowed) {
+        return Vec::new();
+    }
+
+    let index = ProjectIndex::new(classes);
+    let mut violations = BTreeSet::new();
+    for (class, top_level) in flatten_classes(classes) {
+        let source = class.qualified_name();
+        for edge in &class.edges {
+            let target = index.resolve(class, top_level, &edge.target);
+            let matching = |allowed: bool| rules.iter().find(|rule| {
+                rule.allowed == allowed && has_prefix(&source, &rule.from) && has_prefix(&target, &rule.to)
+            });
+            if let (Some(rule), None) = (matching(false), matching(true)) {
+                violations.insert(RuleViolation {
+                    class: source.clone(),
+                    message: format!(
+                        "{} must not depend on {} ({} edge to {})",
+                        rule.from, rule.to, edge.kind, target
+                    ),
+                    file: class.source_file.clone(),
+                    line: Some(edge.line),
+                });
+            }
+        }
+    }
+    violations.into_iter().collect()
+}
+
+pub fn check_thresholds(classes: &[ClassDepsReport], thresholds: &Thresholds) -> Vec<RuleViolation> {
+    let Some(max) = thresholds.max_class_dependencies else {
+        return Vec::new();
+    };
+
+    let index = ProjectIndex::new(classes);
+    flatten_classes(classes).into_iter()
+        .filter_map(|(class, top_level)| {
+            // an import and the uses of the imported type are the same type
+            let used: BTreeSet<String> = class.edges.iter()
+                .filter(|edge| !edge.target.ends_with(".*"))
+                .map(|edge| index.resolve(class, top_level, &edge.target))
+                .collect();
+            (used.len() > max).then(|| RuleViolation {
+                class: class.qualified_name(),
+                message: format!("uses {} types, above the limit of {}", used.len(), max),
+                file: class.source_file.clone(),
+                line: None,
+            })
+        })
+        .collect()
+}
+
+fn layer_of(layers: &[Layer], package: &str) -> Option<usize> {
+    layers.iter().position(|layer| layer.packages.iter().any(|prefix| has_prefix(package, prefix)))
+}
+
+/// Whether `name` is `prefix` or lies below it, `com.foo` covers `com.foo.Bar`
+/// but not `com.foobar`.
+fn has_prefix(name: &str, prefix: &str) -> bool {
+    name.strip_prefix(prefix).is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::analyser::dependency_analyser_lib::analyse_source;
+    use crate::analyser::options::AnalysisOptions;
+
+    fn rules(rules: &[&str]) -> Vec<EdgeRule> {
+        rules.iter().map(|rule| EdgeRule::try_from(rule.to_string()).unwrap()).collect()
+    }
+
+    fn order_classes() -> Vec<ClassDepsReport> {
+        let code = "package com.foo.domain;\n\
+            import com.foo.web.Ctl;\n\
+            class Order {\n    static class Nested {\n        Ctl ctl;\n    }\n}\n";
+        analyse_source(code, "Order.java", &AnalysisOptions::default())
+    }
+
+    #[test]
+    fn edge_rule_violations_name_the_nested_class_and_line() {
+        let violations = check_edge_rules(&order_classes(), &rules(&["com.foo.domain must-not-depend-on com.foo.web"]));
+        let nested: Vec<&RuleViolation> = violations.iter().filter(|v| v.class == "com.foo.domain.Order.Nested").collect();
+        assert_eq!(nested.len(), 1);
+        assert_eq!(nested[0].line, Some(5));
+    }
+
+    #[test]
+    fn exceptions_match_the_fully_nested_name() {
+        let violations = check_edge_rules(&order_classes(), &rules(&[
+            "com.foo.domain must-not-depend-on com.foo.web",
+            "com.foo.domain.Order.Nested may-depend-on com.foo.web",
+        ]));
+        assert!(violations.iter().all(|v| v.class == "com.foo.domain.Order"));
+    }
+
+    #[test]
+    fn thresholds_count_an_imported_type_once() {
+        let code = "package a;\nimport b.Ctl;\nimport c.*;\nclass Order {\n    Ctl ctl;\n    Ctl other() { return ctl; }\n}\n";
+        let classes = analyse_source(code, "Order.java", &AnalysisOptions::default());
+        let limit = |max| Thresholds { max_class_dependencies: Some(max) };
+        assert!(check_thresholds(&classes, &limit(1)).is_empty());
+        assert_eq!(check_thresholds(&classes, &limit(0)).len(), 1);
+    }
+}
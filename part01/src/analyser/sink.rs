@@ -0,0 +1,101 @@
+use std::io::Write;
+use std::path::Path;
+use crate::common::format::{render, ReportFormat};
+use crate::common::types::ClassDepsReport;
+
+/// Destination for the per-file results produced while a project is walked.
+///
+/// Reports are handed over as soon as a file has been analysed, so the walker
+/// does not need to keep them around. Sinks are `Send` so the walk can run in
+/// a spawned task.
+pub trait ReportSink: Send {
+    fn accept(&mut self, file: &Path, classes: Vec<ClassDepsReport>) -> Result<(), String>;
+
+    fn finish(&mut self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Sink dropping every report, used when only the aggregated dependencies matter.
+pub struct DiscardSink;
+
+impl ReportSink for DiscardSink {
+    fn accept(&mut self, _file: &Path, _classes: Vec<ClassDepsReport>) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Sink keeping every report in memory.
+impl ReportSink for Vec<ClassDepsReport> {
+    fn accept(&mut self, _file: &Path, mut classes: Vec<ClassDepsReport>) -> Result<(), String> {
+        self.append(&mut classes);
+        Ok(())
+    }
+}
+
+/// Sink handing each report to `inner` while keeping the parts the project rules
+/// look at, so that rules can be checked once a streamed walk is over. Method
+/// breakdowns and calls are dropped from the kept copies.
+pub struct TeeSink<'a> {
+    inner: &'a mut dyn ReportSink,
+    pub classes: Vec<ClassDepsReport>,
+}
+
+impl<'a> TeeSink<'a> {
+    pub fn new(inner: &'a mut dyn ReportSink) -> Self {
+        Self { inner, classes: Vec::new() }
+    }
+}
+
+impl ReportSink for TeeSink<'_> {
+    fn accept(&mut self, file: &Path, classes: Vec<ClassDepsReport>) -> Result<(), String> {
+        fn slim(class: &ClassDepsReport) -> ClassDepsReport {
+            ClassDepsReport {
+                methods: Vec::new(),
+                calls: Vec::new(),
+                nested_classes: class.nested_classes.iter().map(slim).collect(),
+                ..class.clone()
+            }
+        }
+
+        self.classes.extend(classes.iter().map(slim));
+        self.inner.accept(file, classes)
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.inner.finish()
+    }
+}
+
+/// Sink writing each report to the wrapped writer (a file, stdout, a socket...).
+///
+/// In the plain format lines are sorted file by file only, and the `# file`
+/// header written before each pretty report is left out.
+pub struct WriterSink<W: Write> {
+    writer: W,
+    format: ReportFormat,
+}
+
+impl<W: Write> WriterSink<W> {
+    pub fn new(writer: W) -> Self {
+        Self::with_format(writer, ReportFormat::Pretty)
+    }
+
+    pub fn with_format(writer: W, format: ReportFormat) -> Self {
+        Self { writer, format }
+    }
+}
+
+impl<W: Write + Send> ReportSink for WriterSink<W> {
+    fn accept(&mut self, file: &Path, classes: Vec<ClassDepsReport>) -> Result<(), String> {
+        let write_err = |e: std::io::Error| format!("Failed to write report: {}", e);
+        if self.format == ReportFormat::Pretty {
+            writeln!(self.writer, "# {}", file.display()).map_err(write_err)?;
+        }
+        write!(self.writer, "{}", render(&classes, self.format)).map_err(write_err)
+    }
+
+    fn finish(&mut self) -> Result<(), String> {
+        self.writer.flush().map_err(|e| format!("Failed to flush report: {}", e))
+    }
+}
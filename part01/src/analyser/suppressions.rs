@@ -0,0 +1,171 @@
+use tree_sitter::Node;
+
+const DIRECTIVE: &str = "dep-analyzer:";
+
+/// Commonly used `java.lang` types, which Java code names without importing them.
+const JAVA_LANG_TYPES: &[&str] = &[
+    "Object", "String", "StringBuilder", "StringBuffer", "CharSequence", "Number",
+    "Integer", "Long", "Short", "Byte", "Double", "Float", "Boolean", "Character", "Void",
+    "Math", "StrictMath", "System", "Runtime", "Thread", "ThreadLocal", "Runnable",
+    "Iterable", "Comparable", "AutoCloseable", "Cloneable", "Appendable", "Readable",
+    "Class", "ClassLoader", "Enum", "Record", "Process", "ProcessBuilder", "StackTraceElement",
+    "Throwable", "Exception", "Error", "RuntimeException", "IllegalArgumentException",
+    "IllegalStateException", "NullPointerException", "IndexOutOfBoundsException",
+    "ArrayIndexOutOfBoundsException", "ClassCastException", "ArithmeticException",
+    "UnsupportedOperationException", "NumberFormatException", "InterruptedException",
+    "CloneNotSupportedException", "ClassNotFoundException", "ReflectiveOperationException",
+    "SecurityException", "AssertionError", "OutOfMemoryError", "StackOverflowError",
+    "Override", "Deprecated", "SuppressWarnings", "FunctionalInterface", "SafeVarargs",
+];
+
+/// Dependencies acknowledged inline through comments in a source file:
+///
+/// - `// dep-analyzer:ignore-file` drops every dependency found in the file;
+/// - `// dep-analyzer:ignore com.foo.Bar com.baz.*` drops the listed targets,
+///   matched on the qualified name, on a simple name (`Bar`) resolving to it
+///   through the imports or the package of the file, or on the package prefix
+///   for `.*` entries. Simple names of `java.lang` types, imported implicitly,
+///   also stand for their `java.lang` name.
+///
+/// Directives apply to the whole file, wherever the comment is placed.
+#[derive(Debug, Default, Clone)]
+pub struct Suppressions {
+    pub ignore_file: bool,
+    pub targets: Vec<String>,
+}
+
+impl Suppressions {
+    pub fn collect(root: &Node, code: &str) -> Suppressions {
+        let mut suppressions = Suppressions::default();
+        collect_from_comments(root, code, &mut suppressions);
+        suppressions
+    }
+
+    /// Drops every type of the given packages, as done for the configured stdlib prefixes.
+    pub fn ignore_packages(&mut self, packages: &[String]) {
+        for package in packages {
+            let package = package.trim_end_matches(".*").trim_end_matches('.');
+            self.targets.push(format!("{package}.*"));
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.ignore_file && self.targets.is_empty()
+    }
+
+    /// `package` and `imports` are the ones of the file, used to qualify bare
+    /// names so that `Qux` is caught by `com.baz.*` when `com.baz.Qux` is imported,
+    /// but not by `com.foo.Qux` when `org.other.Qux` is.
+    pub fn is_suppressed(&self, target: &str, package: &str, imports: &[String]) -> bool {
+        if self.ignore_file {
+            return true;
+        }
+        // generics and array markers are not part of the acknowledged name
+        let target = target.split('<').next().unwrap_or(target).trim_end_matches("[]").trim();
+        let names = qualified_candidates(target, package, imports);
+
+        self.targets.iter().any(|ignored| names.iter().any(|name| matches(ignored, name)))
+    }
+
+    fn parse_comment(&mut self, comment: &str) {
+        let body = comment
+            .trim_start_matches("//")
+            .trim_start_matches("/*")
+            .trim_end_matches("*/")
+            .trim();
+        let Some(directive) = body.strip_prefix(DIRECTIVE) else {
+            return;
+        };
+
+        let mut words = directive.split_whitespace();
+        match words.next() {
+            Some("ignore-file") => self.ignore_file = true,
+            Some("ignore") => self.targets.extend(words.map(|w| w.to_string())),
+            _ => (),
+        }
+    }
+}
+
+/// Names `target` may stand for: itself, and for a bare name the single-type
+/// import ending with it or, without one, a class of the package of the file, of
+/// a wildcard imported one or of `java.lang`.
+fn qualified_candidates(target: &str, package: &str, imports: &[String]) -> Vec<String> {
+    let mut names = vec![target.to_string()];
+    if target.contains('.') {
+        return names;
+    }
+    if let Some(import) = imports.iter().find(|import| import.rsplit('.').next() == Some(target)) {
+        names.push(import.clone());
+        return names;
+    }
+    if !package.is_empty() {
+        names.push(format!("{package}.{target}"));
+    }
+    names.extend(imports.iter().filter_map(|import| import.strip_suffix(".*")).map(|on_demand| format!("{on_demand}.{target}")));
+    if JAVA_LANG_TYPES.contains(&target) {
+        names.push(format!("java.lang.{target}"));
+    }
+    names
+}
+
+fn matches(ignored: &str, target: &str) -> bool {
+    match ignored.strip_suffix(".*") {
+        Some(package) => target.strip_prefix(package).is_some_and(|rest| rest.starts_with('.')),
+        None => target == ignored,
+    }
+}
+
+fn collect_from_comments(node: &Node, code: &str, suppressions: &mut Suppressions) {
+    if node.kind() == "line_comment" || node.kind() == "block_comment" {
+        if let Ok(text) = node.utf8_text(code.as_bytes()) {
+            suppressions.parse_comment(text);
+        }
+        return;
+    }
+
+    for i in 0..node.child_count() {
+        collect_from_comments(&node.child(i).unwrap(), code, suppressions);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignoring(targets: &[&str]) -> Suppressions {
+        Suppressions { ignore_file: false, targets: targets.iter().map(|t| t.to_string()).collect() }
+    }
+
+    fn imports(imports: &[&str]) -> Vec<String> {
+        imports.iter().map(|i| i.to_string()).collect()
+    }
+
+    #[test]
+    fn simple_names_match_through_the_imports_and_package() {
+        let suppressions = ignoring(&["com.foo.Bar"]);
+        assert!(suppressions.is_suppressed("com.foo.Bar", "a", &[]));
+        assert!(suppressions.is_suppressed("Bar", "a", &imports(&["com.foo.Bar"])));
+        assert!(suppressions.is_suppressed("Bar", "com.foo", &[]));
+        assert!(suppressions.is_suppressed("Bar<String>", "a", &imports(&["com.foo.*"])));
+    }
+
+    #[test]
+    fn simple_names_resolving_elsewhere_are_kept() {
+        let suppressions = ignoring(&["com.foo.Bar", "com.baz.*"]);
+        assert!(!suppressions.is_suppressed("Bar", "a", &imports(&["org.other.Bar"])));
+        assert!(!suppressions.is_suppressed("Bar", "a", &imports(&["org.other.Bar", "com.foo.*"])));
+        assert!(!suppressions.is_suppressed("Bar", "a", &[]));
+        assert!(suppressions.is_suppressed("Qux", "a", &imports(&["com.baz.Qux"])));
+    }
+
+    #[test]
+    fn java_lang_simple_names_belong_to_java_lang() {
+        let mut suppressions = Suppressions::default();
+        suppressions.ignore_packages(&[String::from("java")]);
+        assert!(suppressions.is_suppressed("String", "a", &[]));
+        assert!(suppressions.is_suppressed("Exception", "a", &[]));
+        assert!(suppressions.is_suppressed("List", "a", &imports(&["java.util.List"])));
+        assert!(!suppressions.is_suppressed("List", "a", &[]));
+        assert!(!suppressions.is_suppressed("String", "a", &imports(&["com.foo.String"])));
+    }
+}
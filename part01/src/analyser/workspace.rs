@@ -0,0 +1,177 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use serde::Serialize;
+use tokio::{sync::Semaphore, task::JoinSet};
+use walkdir::WalkDir;
+use crate::analyser::config::ProjectConfig;
+use crate::analyser::dependency_analyser_lib::get_project_dependencies_with_options;
+use crate::analyser::index::{flatten_classes, ProjectIndex};
+use crate::analyser::options::AnalysisOptions;
+use crate::common::types::{ClassDepsReport, FileDiagnostic, ProjectDepsReport};
+
+/// Files marking the top folder of a repository or build.
+const PROJECT_MARKERS: [&str; 4] = [".git", "pom.xml", "settings.gradle", "settings.gradle.kts"];
+
+/// Project analysis of one repository of the workspace.
+#[derive(Debug, Serialize)]
+pub struct RepositoryReport {
+    /// Folder of the repository relative to the workspace, `.` for the workspace itself
+    pub name: String,
+    pub root: PathBuf,
+    pub report: ProjectDepsReport,
+}
+
+/// Uses of the classes of a repository by the ones of another.
+#[derive(Debug, Clone, Serialize)]
+pub struct InterRepoEdge {
+    pub from_repository: String,
+    pub to_repository: String,
+    /// `pkg.Class -> pkg.Target` pairs behind the edge, `pkg.*` for wildcard imports
+    pub uses: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WorkspaceReport {
+    pub workspace: String,
+    pub repositories: Vec<RepositoryReport>,
+    pub edges: Vec<InterRepoEdge>,
+    /// Repositories that could not be analysed, left out of the report
+    pub diagnostics: Vec<FileDiagnostic>,
+}
+
+/// Folders below `workspace` holding a project marker. The search does not go
+/// below a project root, so the modules of a Maven or Gradle build are analysed
+/// together with it. The workspace itself, often a repository as well, is only
+/// a project when none is found below it.
+pub fn discover_projects(workspace: &Path) -> Vec<PathBuf> {
+    let is_project = |folder: &Path| PROJECT_MARKERS.iter().any(|marker| folder.join(marker).exists());
+
+    let mut roots = Vec::new();
+    let mut walker = WalkDir::new(workspace).min_depth(1).sort_by_file_name().into_iter();
+    while let Some(entry) = walker.next() {
+        let Ok(entry) = entry else {
+            continue;
+        };
+        if !entry.file_type().is_dir() {
+            continue;
+        }
+        if is_project(entry.path()) {
+            roots.push(entry.path().to_path_buf());
+            walker.skip_current_dir();
+        }
+    }
+    if roots.is_empty() && is_project(workspace) {
+        roots.push(workspace.to_path_buf());
+    }
+    roots
+}
+
+pub async fn get_workspace_dependencies(workspace: String) -> Result<WorkspaceReport, String> {
+    get_workspace_dependencies_with_options(workspace, &AnalysisOptions::default()).await
+}
+
+/// Analyses every project found in `workspace`, at most `options.concurrency`
+/// at a time, and links the classes of one project used by another. The config
+/// file found from each project root is applied on top of `options`. A project
+/// failing to be analysed is reported in the diagnostics, the others are kept.
+pub async fn get_workspace_dependencies_with_options(
+    workspace: String,
+    options: &AnalysisOptions,
+) -> Result<WorkspaceReport, String> {
+    let roots = discover_projects(Path::new(&workspace));
+    if roots.is_empty() {
+        return Err(format!("No project found in {}, expected .git, pom.xml or settings.gradle", workspace));
+    }
+
+    let limit = Arc::new(Semaphore::new(options.concurrency.max(1)));
+    let mut tasks = JoinSet::new();
+    for root in roots {
+        let options = options.clone();
+        let limit = limit.clone();
+        let name = match root.strip_prefix(&workspace) {
+            Ok(relative) if relative.as_os_str().is_empty() => String::from("."),
+            Ok(relative) => relative.to_string_lossy().into_owned(),
+            Err(_) => root.to_string_lossy().into_owned(),
+        };
+        tasks.spawn(async move {
+            let _permit = limit.acquire_owned().await;
+            let report = match repository_options(&root, options) {
+                Ok(options) => get_project_dependencies_with_options(vec![root.to_string_lossy().into_owned()], &options).await,
+                Err(e) => Err(e),
+            };
+            (name, root, report)
+        });
+    }
+
+    let mut repositories = Vec::new();
+    let mut diagnostics = Vec::new();
+    while let Some(joined) = tasks.join_next().await {
+        match joined {
+            Ok((name, root, Ok(report))) => repositories.push(RepositoryReport { name, root, report }),
+            Ok((_, root, Err(message))) => diagnostics.push(FileDiagnostic { file: root.to_string_lossy().into_owned(), message }),
+            Err(e) => diagnostics.push(FileDiagnostic {
+                file: workspace.clone(),
+                message: format!("Analysis task failed: {}", e),
+            }),
+        }
+    }
+    repositories.sort_by(|a, b| a.name.cmp(&b.name));
+    diagnostics.sort();
+
+    let edges = inter_repo_edges(&repositories);
+    Ok(WorkspaceReport { workspace, repositories, edges, diagnostics })
+}
+
+/// `options` with the `.depanalyzer.toml` of the repository applied, each
+/// repository having its own excludes and stdlib prefixes. Classes are always
+/// retained, the repositories being linked through them.
+fn repository_options(root: &Path, mut options: AnalysisOptions) -> Result<AnalysisOptions, String> {
+    if let Some((_, config)) = ProjectConfig::discover(root)? {
+        config.apply(&mut options);
+    }
+    options.retain_classes = true;
+    Ok(options)
+}
+
+fn inter_repo_edges(repositories: &[RepositoryReport]) -> Vec<InterRepoEdge> {
+    let classes: Vec<ClassDepsReport> = repositories.iter()
+        .flat_map(|repository| repository.report.classes.iter().cloned())
+        .collect();
+    let index = ProjectIndex::new(&classes);
+
+    let mut class_owner: BTreeMap<String, &str> = BTreeMap::new();
+    let mut package_owners: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+    for repository in repositories {
+        for (class, _) in flatten_classes(&repository.report.classes) {
+            class_owner.insert(class.qualified_name(), &repository.name);
+            package_owners.entry(&class.package_name).or_default().insert(&repository.name);
+        }
+    }
+
+    let mut uses: BTreeMap<(&str, &str), BTreeSet<String>> = BTreeMap::new();
+    for repository in repositories {
+        let from_repository = repository.name.as_str();
+        for (class, top_level) in flatten_classes(&repository.report.classes) {
+            let from = class.qualified_name();
+            for edge in &class.edges {
+                let target = index.resolve(class, top_level, &edge.target);
+                let owners: Vec<&str> = match target.strip_suffix(".*") {
+                    Some(package) => package_owners.get(package).into_iter().flatten().copied().collect(),
+                    None => class_owner.get(&target).into_iter().copied().collect(),
+                };
+                for to_repository in owners.into_iter().filter(|owner| *owner != from_repository) {
+                    uses.entry((from_repository, to_repository)).or_default().insert(format!("{from} -> {target}"));
+                }
+            }
+        }
+    }
+
+    uses.into_iter()
+        .map(|((from_repository, to_repository), uses)| InterRepoEdge {
+            from_repository: from_repository.to_string(),
+            to_repository: to_repository.to_string(),
+            uses: uses.into_iter().collect(),
+        })
+        .collect()
+}
@@ -0,0 +1,180 @@
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use assignment_02_part01::common::format::ReportFormat;
+use assignment_02_part01::export::report::ExportFormat;
+
+#[derive(Parser)]
+#[command(about = "Asynchronous dependency analyser for Java projects")]
+pub struct Cli {
+    /// Without a command the bundled test files are analysed
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Dependencies of the classes declared in a single source file
+    Class {
+        file: String,
+        /// pretty or plain, the latter being one `class -> dep [kind]` line per dependency
+        #[arg(long, default_value_t = ReportFormat::Pretty)]
+        format: ReportFormat,
+        /// List the dependencies of every method and constructor instead, in the chosen format
+        #[arg(long)]
+        methods: bool,
+        /// With --methods, only list the methods using this type
+        #[arg(long, requires = "methods")]
+        using: Option<String>,
+    },
+    /// Dependencies of all the classes in a package folder
+    Package {
+        folder: String,
+        /// Analyse the sub-packages too, printing one line per package
+        #[arg(long)]
+        recursive: bool,
+    },
+    /// Dependencies of a project, possibly spread over several source roots
+    Project(ProjectArgs),
+    /// Write one diagram per package, with its direct neighbours, and an index page
+    Partition {
+        #[arg(required = true)]
+        roots: Vec<String>,
+        /// Folder the diagrams are written to
+        #[arg(long, default_value = "dep-partitions")]
+        out: String,
+    },
+    /// Write several report formats from a single analysis
+    Export {
+        #[arg(required = true)]
+        roots: Vec<String>,
+        /// Comma separated list of json, dot, html and csv
+        #[arg(long, value_delimiter = ',', default_value = "json,dot,html,csv")]
+        formats: Vec<ExportFormat>,
+        /// Folder the reports are written to
+        #[arg(long, default_value = "dep-report")]
+        out: String,
+        /// Keep the line of code behind every edge, for the json and html reports
+        #[arg(long)]
+        snippets: bool,
+    },
+    /// Analyse every repository of a folder, found by .git, pom.xml or settings.gradle, and link them
+    Workspace {
+        folder: String,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Classes of each package used from other packages, against the internal ones
+    ApiSurface {
+        #[arg(required = true)]
+        roots: Vec<String>,
+        /// Print the report as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Calls between the methods of the project classes
+    CallGraph(CallGraphArgs),
+    /// Generate a synthetic Java project, for benchmarks and demos
+    GenFixture(GenFixtureArgs),
+    /// Keep a project analysis in memory and answer requests over a local socket
+    #[cfg(unix)]
+    Daemon(DaemonArgs),
+}
+
+/// Flags given here take precedence over the `.depanalyzer.toml` found from the first root.
+#[derive(Args)]
+pub struct ProjectArgs {
+    #[arg(required = true)]
+    pub roots: Vec<String>,
+    /// Print each file report as soon as it is analysed instead of keeping them in memory; when rules are configured, slimmed copies of the reports are still kept to check them once the walk is over
+    #[arg(long)]
+    pub stream: bool,
+    /// pretty or plain, the latter being one `class -> dep [kind]` line per dependency
+    #[arg(long)]
+    pub format: Option<ReportFormat>,
+    /// Glob of the files to skip, relative to the root; replaces the configured ones
+    #[arg(long)]
+    pub exclude: Vec<String>,
+    /// Extension besides java of the files to analyse (e.g. jav); replaces the configured ones
+    #[arg(long = "extension")]
+    pub extra_extensions: Vec<String>,
+    /// Package whose types are not reported (e.g. java); replaces the configured ones
+    #[arg(long = "stdlib-prefix")]
+    pub stdlib_prefixes: Vec<String>,
+    /// Report classes using more distinct types than this
+    #[arg(long)]
+    pub max_class_deps: Option<usize>,
+    /// Skip files bigger than this many bytes [default: 10 MiB]
+    #[arg(long, value_name = "BYTES")]
+    pub max_file_size: Option<u64>,
+    /// Do not look for a .depanalyzer.toml file
+    #[arg(long)]
+    pub no_config: bool,
+}
+
+#[derive(Args)]
+pub struct GenFixtureArgs {
+    /// Folder the sources are written to, created when missing
+    pub out: String,
+    /// Root package of the generated project
+    #[arg(long, default_value = "fixture")]
+    pub package: String,
+    /// Levels of sub-packages below the root package
+    #[arg(long, default_value_t = 2)]
+    pub depth: usize,
+    /// Sub-packages of every package
+    #[arg(long, default_value_t = 2)]
+    pub branching: usize,
+    /// Classes of every package
+    #[arg(long, default_value_t = 3)]
+    pub classes: usize,
+    /// Add class and package dependency cycles
+    #[arg(long)]
+    pub cycles: bool,
+    /// Levels of static nested classes inside every class
+    #[arg(long, default_value_t = 0)]
+    pub nested: usize,
+}
+
+#[derive(Args)]
+pub struct CallGraphArgs {
+    #[arg(required = true)]
+    pub roots: Vec<String>,
+    #[arg(long, value_enum, default_value_t = CallGraphFormat::Dot)]
+    pub format: CallGraphFormat,
+    /// Only list the methods affected by a change to this one, given as pkg.Class.method
+    #[arg(long)]
+    pub impact: Option<String>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CallGraphFormat {
+    /// Graphviz digraph of the method calls
+    Dot,
+    /// Caller and callee of every call
+    Json,
+}
+
+#[cfg(unix)]
+#[derive(Args)]
+pub struct DaemonArgs {
+    #[command(subcommand)]
+    pub action: DaemonAction,
+    /// Socket of the daemon, depanalyzer.sock in the temporary folder by default
+    #[arg(long, global = true)]
+    pub socket: Option<String>,
+}
+
+#[cfg(unix)]
+#[derive(Subcommand)]
+pub enum DaemonAction {
+    /// Start the daemon in the foreground, analysing the roots when given
+    Serve { roots: Vec<String> },
+    /// Show what the running daemon holds
+    Status,
+    /// Analyse the roots, or only the changed files when they are the ones already analysed
+    Analyze { roots: Vec<String> },
+    /// Types used by a class and the classes using it, given as pkg.Class
+    Query { class: String },
+    /// Stop the running daemon
+    Stop,
+}
@@ -0,0 +1,125 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use serde::Deserialize;
+use crate::common::types::ClassDepsReport;
+
+/// How class reports are rendered as text.
+///
+/// `Pretty` is the indented, human oriented `Display` of [`ClassDepsReport`].
+///
+/// `Plain` is a stable format meant for golden files and shell pipelines, one
+/// dependency per line:
+///
+/// ```text
+/// pcd.ass02.MyClass -> A [field]
+/// pcd.ass02.MyClass -> pcd.ass02.foopack.D [import]
+/// ```
+///
+/// The class is written with its qualified name (`pkg.Outer.Inner` for nested
+/// classes), the dependency as it appears in the source and the kind as one of
+/// `import`, `extends`, `implements`, `field`, `parameter`, `return`, `local`,
+/// `new` and `reflective`. Lines are sorted and deduplicated, and classes
+/// without dependencies produce no line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Pretty,
+    Plain,
+}
+
+impl FromStr for ReportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "pretty" => Ok(ReportFormat::Pretty),
+            "plain" => Ok(ReportFormat::Plain),
+            _ => Err(format!("Unknown format {}, expected pretty or plain", s)),
+        }
+    }
+}
+
+impl Display for ReportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReportFormat::Pretty => write!(f, "pretty"),
+            ReportFormat::Plain => write!(f, "plain"),
+        }
+    }
+}
+
+/// Renders the reports in `format`, every class or line ending with a newline.
+pub fn render(classes: &[ClassDepsReport], format: ReportFormat) -> String {
+    let mut text = String::new();
+    match format {
+        ReportFormat::Pretty => {
+            for class in classes {
+                text.push_str(&format!("{class}\n"));
+            }
+        },
+        ReportFormat::Plain => {
+            for line in plain_lines(classes) {
+                text.push_str(&line);
+                text.push('\n');
+            }
+        },
+    }
+    text
+}
+
+/// Per method breakdown of the classes, only the methods using `using` when
+/// given. The pretty format lists the dependencies of a method on its line, the
+/// plain one writes a `pkg.Class.method(Params) -> dep [kind]` line per dependency.
+pub fn render_methods(classes: &[ClassDepsReport], using: Option<&str>, format: ReportFormat) -> String {
+    fn collect(class: &ClassDepsReport, using: Option<&str>, format: ReportFormat, lines: &mut Vec<String>) {
+        let methods = match using {
+            Some(target) => class.methods_using(target),
+            None => class.methods.iter().collect(),
+        };
+        for method in methods {
+            let name = format!("{}.{}", class.qualified_name(), method.signature);
+            match format {
+                ReportFormat::Pretty => {
+                    let deps: Vec<String> = method.deps.iter().map(|dep| dep.to_string()).collect();
+                    lines.push(format!("{}: {}", name, deps.join(", ")));
+                },
+                ReportFormat::Plain => {
+                    lines.extend(method.deps.iter().map(|dep| format!("{} -> {} [{}]", name, dep.target, dep.kind)));
+                },
+            }
+        }
+        for nested in &class.nested_classes {
+            collect(nested, using, format, lines);
+        }
+    }
+
+    let mut lines = Vec::new();
+    for class in classes {
+        collect(class, using, format, &mut lines);
+    }
+    if format == ReportFormat::Plain {
+        lines.sort();
+        lines.dedup();
+    }
+    lines.into_iter().map(|line| line + "\n").collect()
+}
+
+/// The sorted lines of the plain format.
+pub fn plain_lines(classes: &[ClassDepsReport]) -> Vec<String> {
+    fn collect(class: &ClassDepsReport, name: String, lines: &mut BTreeSet<String>) {
+        for edge in &class.edges {
+            lines.insert(format!("{} -> {} [{}]", name, edge.target, edge.kind));
+        }
+        for nested in &class.nested_classes {
+            collect(nested, format!("{}.{}", name, nested.class_name), lines);
+        }
+    }
+
+    let mut lines = BTreeSet::new();
+    for class in classes {
+        collect(class, class.qualified_name(), &mut lines);
+    }
+    lines.into_iter().collect()
+}
@@ -1,13 +1,219 @@
 use std::fmt::{Display, Formatter};
+use serde::{Serialize, Serializer};
 
-#[derive(Clone)]
+/// Syntactic position a dependency was found in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum DependencyKind {
+    Import,
+    Extends,
+    Implements,
+    Field,
+    Parameter,
+    ReturnType,
+    LocalVariable,
+    Instantiation,
+    /// `Class.forName("a.B")`, `loader.loadClass("a.B")` or a `B.class` literal
+    Reflective,
+}
+
+/// Visibility of the member creating a dependency.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum AccessModifier {
+    Public,
+    Protected,
+    PackagePrivate,
+    Private,
+}
+
+/// Shape of a type declared in the analysed sources.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TypeKind {
+    Class,
+    AbstractClass,
+    Interface,
+}
+
+/// A single dependency edge from a class to a type it uses.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+pub struct Dependency {
+    pub target: String,
+    pub kind: DependencyKind,
+    /// Visibility of the source member, `None` for imports
+    pub access: Option<AccessModifier>,
+    /// Shape of the target, only known when it is declared in the analysed sources
+    pub target_kind: Option<TypeKind>,
+    /// Line of the source file the dependency comes from, starting at 1
+    pub line: usize,
+    /// That line of code, trimmed, only kept when `AnalysisOptions::snippets` is set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub snippet: Option<String>,
+}
+
+impl Display for DependencyKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DependencyKind::Import => "import",
+            DependencyKind::Extends => "extends",
+            DependencyKind::Implements => "implements",
+            DependencyKind::Field => "field",
+            DependencyKind::Parameter => "parameter",
+            DependencyKind::ReturnType => "return",
+            DependencyKind::LocalVariable => "local",
+            DependencyKind::Instantiation => "new",
+            DependencyKind::Reflective => "reflective",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl Display for AccessModifier {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AccessModifier::Public => "public",
+            AccessModifier::Protected => "protected",
+            AccessModifier::PackagePrivate => "package",
+            AccessModifier::Private => "private",
+        };
+        write!(f, "{name}")
+    }
+}
+
+impl Display for TypeKind {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            TypeKind::Class => "class",
+            TypeKind::AbstractClass => "abstract class",
+            TypeKind::Interface => "interface",
+        };
+        write!(f, "{name}")
+    }
+}
+
+// serialized with the same names as in the text reports
+impl Serialize for DependencyKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for AccessModifier {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Serialize for TypeKind {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl Display for Dependency {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}", self.target, self.kind)?;
+        if let Some(access) = self.access {
+            write!(f, ", {access}")?;
+        }
+        write!(f, "]")?;
+        if let Some(target_kind) = self.target_kind {
+            write!(f, " ({target_kind})")?;
+        }
+        Ok(())
+    }
+}
+
+/// Dependencies coming from a single method or constructor.
+#[derive(Debug, Clone, Serialize)]
+pub struct MethodDepsReport {
+    /// Name and parameter types, e.g. `m(E, List<String>)`
+    pub signature: String,
+    /// Same edges as the class ones, `target_kind` is left unset
+    pub deps: Vec<Dependency>,
+}
+
+/// A method invocation found in a class body, kept as written until the call
+/// graph resolves the receiver against the project classes.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct MethodCall {
+    /// Name of the calling method or constructor
+    pub caller: String,
+    /// Type of the receiver as written in the source, `None` for calls on `this`
+    pub receiver: Option<String>,
+    pub method: String,
+}
+
+/// Size of a class declaration, gathered with its dependencies.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct ClassSize {
+    /// Lines holding code, nested classes included, blank and comment-only lines left out
+    pub lines: usize,
+    /// Methods declared in the class body, constructors left out
+    pub methods: usize,
+    /// Fields declared in the class body, `int a, b;` counting two
+    pub fields: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct ClassDepsReport {
     pub class_name: String,
+    pub package_name: String,
+    /// Names of the classes the class is nested in, outermost first
+    pub enclosing_classes: Vec<String>,
+    pub type_kind: TypeKind,
     pub class_deps: Vec<String>,
-    pub nested_classes: Vec<ClassDepsReport>
+    /// Typed edges behind `class_deps`, annotated with member visibility and target shape
+    pub edges: Vec<Dependency>,
+    pub nested_classes: Vec<ClassDepsReport>,
+    /// Per method breakdown, only filled when `AnalysisOptions::per_method` is set
+    pub methods: Vec<MethodDepsReport>,
+    /// Invocations with a known receiver type, only filled when `AnalysisOptions::call_graph` is set
+    pub calls: Vec<MethodCall>,
+    pub size: ClassSize,
+    /// File the class is declared in, as given to the analysis
+    pub source_file: String,
+    /// Source root the class was found under, set by multi-root project analysis
+    pub source_root: Option<String>,
 }
 
 impl ClassDepsReport {
+    pub fn label_root(&mut self, root: &str) {
+        self.source_root = Some(root.to_string());
+        for nes_class in self.nested_classes.iter_mut() {
+            nes_class.label_root(root);
+        }
+    }
+
+    /// Fully qualified name, `pkg.Outer.Inner` for nested classes, without the
+    /// package for classes in the default package
+    pub fn qualified_name(&self) -> String {
+        let mut parts: Vec<&str> = Vec::new();
+        if !self.package_name.is_empty() {
+            parts.push(&self.package_name);
+        }
+        parts.extend(self.enclosing_classes.iter().map(String::as_str));
+        parts.push(&self.class_name);
+        parts.join(".")
+    }
+
+    /// Methods and constructors using `target`, given either as written in the
+    /// source or by its simple name (generics are ignored).
+    pub fn methods_using(&self, target: &str) -> Vec<&MethodDepsReport> {
+        self.methods.iter()
+            .filter(|method| method.deps.iter().any(|dep| {
+                let name = dep.target.split('<').next().unwrap_or(&dep.target);
+                name == target || name.rsplit('.').next() == Some(target)
+            }))
+            .collect()
+    }
+
+    /// Targets of the import edges, only populated on top-level classes
+    pub fn imports(&self) -> Vec<&str> {
+        self.edges.iter()
+            .filter(|dep| dep.kind == DependencyKind::Import)
+            .map(|dep| dep.target.as_str())
+            .collect()
+    }
+
     pub fn get_dependencies(&self) -> Vec<String> {
         let mut dependencies: Vec<String> = self.class_deps.clone();
         for nes_class in self.nested_classes.clone() {
@@ -27,7 +233,14 @@ fn get_string_with_nesting_level(class: ClassDepsReport, nes_level: i8) -> Strin
     }
 
     let mut report = String::new();
-    report.push_str(format!("{tab}|{}\n", class.class_name).as_str());
+    match &class.source_root {
+        Some(root) if nes_level == 0 => report.push_str(format!("{tab}|{} ({root})\n", class.class_name).as_str()),
+        _ => report.push_str(format!("{tab}|{}\n", class.class_name).as_str()),
+    }
+    report.push_str(format!(
+        "{tab}|  size: {} lines, {} methods, {} fields\n",
+        class.size.lines, class.size.methods, class.size.fields
+    ).as_str());
     report.push_str(format!("{tab}|  dependencies:\n").as_str());
     for dep in class.class_deps {
         report.push_str(format!("{tab}|    {}\n", dep).as_str());
@@ -51,12 +264,60 @@ impl Display for ClassDepsReport {
 #[allow(dead_code)]
 pub struct PackageDepsReport {
     pub package_name: String,
-    pub package_deps: Vec<String>
+    pub package_deps: Vec<String>,
+    /// Files of the package that could not be analysed
+    pub diagnostics: Vec<FileDiagnostic>,
+    /// Reports of the sub-folders, only filled by recursive analysis
+    pub sub_packages: Vec<PackageDepsReport>,
+}
+
+impl PackageDepsReport {
+    /// Dependencies of this package and of all its sub-packages.
+    pub fn all_dependencies(&self) -> Vec<String> {
+        let mut dependencies = self.package_deps.clone();
+        for sub_package in &self.sub_packages {
+            dependencies.append(&mut sub_package.all_dependencies());
+        }
+        dependencies.sort();
+        dependencies.dedup();
+        dependencies
+    }
+
+    /// This package followed by every sub-package, depth first.
+    pub fn flatten(&self) -> Vec<&PackageDepsReport> {
+        let mut packages = vec![self];
+        for sub_package in &self.sub_packages {
+            packages.append(&mut sub_package.flatten());
+        }
+        packages
+    }
+}
+
+/// Problem met while analysing a single file, reported instead of failing the whole run.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct FileDiagnostic {
+    pub file: String,
+    pub message: String,
+}
+
+impl Display for FileDiagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.file, self.message)
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize)]
 #[allow(dead_code)]
 pub struct ProjectDepsReport {
-    pub project_folder: String,
-    pub project_deps: Vec<String>
+    pub project_folders: Vec<String>,
+    pub project_deps: Vec<String>,
+    /// Every analysed class labelled with its source root; empty unless the
+    /// analysis was asked to retain them
+    pub classes: Vec<ClassDepsReport>,
+    /// Whether `classes` holds every analysed class, false for a streamed report
+    #[serde(skip)]
+    pub classes_retained: bool,
+    /// Folders that could not be walked and files that were skipped or failed
+    /// the analysis
+    pub diagnostics: Vec<FileDiagnostic>,
 }
\ No newline at end of file
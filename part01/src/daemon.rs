@@ -0,0 +1,279 @@
+use std::collections::{BTreeSet, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{watch, Mutex};
+use tokio::time::timeout;
+use walkdir::WalkDir;
+use crate::analyser::config::ProjectConfig;
+use crate::analyser::dependency_analyser_lib::{
+    get_project_dependencies_with_options, update_project_report_with_options,
+};
+use crate::analyser::graph::ClassGraph;
+use crate::analyser::options::{is_excluded, AnalysisOptions};
+use crate::common::types::ProjectDepsReport;
+
+/// Socket used when none is given, in the temporary folder.
+pub fn default_socket_path() -> PathBuf {
+    std::env::temp_dir().join("depanalyzer.sock")
+}
+
+/// How long a client may take to send its request line.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request, sent as a single line of JSON such as
+/// `{"command":"analyze","roots":["/path/with spaces"]}`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "lowercase")]
+pub enum DaemonRequest {
+    /// Analysed roots, classes, files and timings
+    Status,
+    /// Analyses the given roots, or re-analyses the files changed since the
+    /// last run when the roots are the same or omitted
+    Analyze {
+        #[serde(default)]
+        roots: Vec<String>,
+    },
+    /// Types used by the class and classes using it
+    Query { class: String },
+    /// Shuts the daemon down
+    Stop,
+}
+
+/// Analysis kept in memory between requests.
+struct DaemonState {
+    roots: Vec<String>,
+    options: AnalysisOptions,
+    report: Option<ProjectDepsReport>,
+    graph: ClassGraph,
+    /// Modification time of every analysed file, to find the changed ones
+    stamps: HashMap<PathBuf, SystemTime>,
+    started: Instant,
+    analyses: usize,
+    last_duration: Option<Duration>,
+}
+
+/// Serves [`DaemonRequest`]s on `socket` until a `stop` one arrives, each one
+/// answered with text before the connection is closed. Connections are handled
+/// concurrently, requests touching the analysis waiting for each other.
+///
+/// Answers to failed requests start with `error: `. The roots, when given, are
+/// analysed before the first request is accepted.
+pub async fn serve(socket: &Path, roots: Vec<String>) -> Result<(), String> {
+    if socket.exists() {
+        if UnixStream::connect(socket).await.is_ok() {
+            return Err(format!("A daemon is already listening on {}", socket.display()));
+        }
+        // left behind by a daemon that did not stop cleanly
+        if let Err(e) = std::fs::remove_file(socket) {
+            return Err(format!("Failed to remove {}: {}", socket.display(), e));
+        }
+    }
+
+    let mut state = DaemonState {
+        roots: Vec::new(),
+        options: AnalysisOptions::default(),
+        report: None,
+        graph: ClassGraph::default(),
+        stamps: HashMap::new(),
+        started: Instant::now(),
+        analyses: 0,
+        last_duration: None,
+    };
+    if !roots.is_empty() {
+        state.analyze(roots).await?;
+    }
+    let state = Arc::new(Mutex::new(state));
+
+    let listener = match UnixListener::bind(socket) {
+        Ok(listener) => listener,
+        Err(e) => return Err(format!("Failed to listen on {}: {}", socket.display(), e)),
+    };
+    let (stop_sender, mut stop_receiver) = watch::channel(false);
+    loop {
+        let stream = tokio::select! {
+            connection = listener.accept() => match connection {
+                Ok((stream, _)) => stream,
+                Err(e) => {
+                    eprintln!("Failed to accept a connection: {}", e);
+                    continue;
+                }
+            },
+            _ = stop_receiver.changed() => break,
+        };
+        tokio::spawn(handle_connection(stream, state.clone(), stop_sender.clone()));
+    }
+
+    let _ = std::fs::remove_file(socket);
+    Ok(())
+}
+
+/// Reads the request of a client and writes back the answer.
+async fn handle_connection(stream: UnixStream, state: Arc<Mutex<DaemonState>>, stop: watch::Sender<bool>) {
+    let (read_half, mut write_half) = stream.into_split();
+    let mut line = String::new();
+    let answer = match timeout(REQUEST_TIMEOUT, BufReader::new(read_half).read_line(&mut line)).await {
+        Err(_) => Err(format!("no request received within {}s", REQUEST_TIMEOUT.as_secs())),
+        Ok(Err(e)) => Err(format!("Failed to read the request: {}", e)),
+        Ok(Ok(_)) => match serde_json::from_str::<DaemonRequest>(&line) {
+            Ok(DaemonRequest::Stop) => {
+                let _ = stop.send(true);
+                Ok(String::from("stopped\n"))
+            },
+            Ok(request) => state.lock().await.handle(request).await,
+            Err(e) => Err(format!("Invalid request '{}': {}", line.trim(), e)),
+        },
+    };
+    let answer = answer.unwrap_or_else(|e| format!("error: {}\n", e));
+    // the client may be gone already, nothing to do about it
+    let _ = write_half.write_all(answer.as_bytes()).await;
+}
+
+/// Sends a single request to the daemon listening on `socket` and returns its answer.
+pub async fn request(socket: &Path, request: &DaemonRequest) -> Result<String, String> {
+    let mut stream = match UnixStream::connect(socket).await {
+        Ok(stream) => stream,
+        Err(e) => return Err(format!("Failed to connect to the daemon on {}: {}", socket.display(), e)),
+    };
+    let line = match serde_json::to_string(request) {
+        Ok(line) => line,
+        Err(e) => return Err(format!("Failed to encode the request: {}", e)),
+    };
+    if let Err(e) = stream.write_all(format!("{line}\n").as_bytes()).await {
+        return Err(format!("Failed to send the request: {}", e));
+    }
+
+    let mut answer = String::new();
+    if let Err(e) = stream.read_to_string(&mut answer).await {
+        return Err(format!("Failed to read the answer: {}", e));
+    }
+    match answer.strip_prefix("error: ") {
+        Some(message) => Err(message.trim_end().to_string()),
+        None => Ok(answer),
+    }
+}
+
+impl DaemonState {
+    async fn handle(&mut self, request: DaemonRequest) -> Result<String, String> {
+        match request {
+            DaemonRequest::Status => Ok(self.status()),
+            DaemonRequest::Analyze { roots } => self.analyze(roots).await,
+            DaemonRequest::Query { class } => self.query(&class),
+            DaemonRequest::Stop => Ok(String::from("stopped\n")),
+        }
+    }
+
+    fn status(&self) -> String {
+        let mut status = format!("uptime: {}s\n", self.started.elapsed().as_secs());
+        match &self.report {
+            Some(report) => {
+                status.push_str(&format!("roots: {}\n", self.roots.join(", ")));
+                status.push_str(&format!("files: {}\n", self.stamps.len()));
+                status.push_str(&format!("classes: {}\n", report.classes.len()));
+            },
+            None => status.push_str("roots: none analysed yet\n"),
+        }
+        status.push_str(&format!("analyses: {}\n", self.analyses));
+        if let Some(duration) = self.last_duration {
+            status.push_str(&format!("last analysis: {}ms\n", duration.as_millis()));
+        }
+        status
+    }
+
+    async fn analyze(&mut self, roots: Vec<String>) -> Result<String, String> {
+        let start = Instant::now();
+        let incremental = self.report.is_some() && (roots.is_empty() || roots == self.roots);
+        let answer = if incremental {
+            let stamps = collect_stamps(&self.roots, &self.options)?;
+            let mut changed: Vec<PathBuf> = stamps.iter()
+                .filter(|(file, modified)| self.stamps.get(*file) != Some(modified))
+                .map(|(file, _)| file.clone())
+                .collect();
+            changed.extend(self.stamps.keys().filter(|file| !stamps.contains_key(*file)).cloned());
+
+            if !changed.is_empty() {
+                // the previous report stays in place should a file fail to be analysed
+                let previous = self.report.clone().unwrap();
+                self.report = Some(update_project_report_with_options(previous, &changed, &self.options).await?);
+            }
+            self.stamps = stamps;
+            format!("{} changed files analysed again", changed.len())
+        } else {
+            if roots.is_empty() {
+                return Err(String::from("analyze needs the project roots the first time"));
+            }
+            // same .depanalyzer.toml handling as the project command
+            let config = ProjectConfig::discover(Path::new(&roots[0]))?.map(|(_, config)| config).unwrap_or_default();
+            let mut options = AnalysisOptions { retain_classes: true, ..AnalysisOptions::default() };
+            config.apply(&mut options);
+            // nothing is replaced until the new analysis succeeded
+            let stamps = collect_stamps(&roots, &options)?;
+            let report = get_project_dependencies_with_options(roots.clone(), &options).await?;
+            self.options = options;
+            self.roots = roots;
+            self.stamps = stamps;
+            self.report = Some(report);
+            format!("{} files analysed", self.stamps.len())
+        };
+
+        self.graph = ClassGraph::build(&self.report.as_ref().unwrap().classes);
+        self.analyses += 1;
+        self.last_duration = Some(start.elapsed());
+        Ok(format!("{} in {}ms\n", answer, start.elapsed().as_millis()))
+    }
+
+    fn query(&self, class: &str) -> Result<String, String> {
+        if self.report.is_none() {
+            return Err(String::from("no project analysed yet"));
+        }
+        if !self.graph.is_project_class(class) {
+            return Err(format!("{} is not a class of the analysed project", class));
+        }
+
+        let mut uses = BTreeSet::new();
+        let mut used_by = BTreeSet::new();
+        for ((from, to), kinds) in &self.graph.edges {
+            let kinds: Vec<String> = kinds.iter().map(|kind| kind.to_string()).collect();
+            if from == class {
+                uses.insert(format!("  {} [{}]", to, kinds.join(", ")));
+            }
+            if to == class {
+                used_by.insert(format!("  {} [{}]", from, kinds.join(", ")));
+            }
+        }
+
+        let mut answer = format!("{class} uses:\n");
+        for line in uses {
+            answer.push_str(&line);
+            answer.push('\n');
+        }
+        answer.push_str(&format!("{class} is used by:\n"));
+        for line in used_by {
+            answer.push_str(&line);
+            answer.push('\n');
+        }
+        Ok(answer)
+    }
+}
+
+/// Modification times of the files the walker would analyse, spelled as it
+/// does so that they match the reports' source files.
+fn collect_stamps(roots: &[String], options: &AnalysisOptions) -> Result<HashMap<PathBuf, SystemTime>, String> {
+    let excluded = options.exclude_set()?;
+    let mut stamps = HashMap::new();
+    for root in roots {
+        for entry in WalkDir::new(root).into_iter().filter_map(|e| e.ok()) {
+            if is_excluded(&excluded, Path::new(root), entry.path()) {
+                continue;
+            }
+            if entry.path().is_file() && options.is_java_source(entry.path()) {
+                let modified = entry.metadata().ok().and_then(|metadata| metadata.modified().ok());
+                stamps.insert(entry.path().to_path_buf(), modified.unwrap_or(SystemTime::UNIX_EPOCH));
+            }
+        }
+    }
+    Ok(stamps)
+}
@@ -0,0 +1,3 @@
+pub mod dot;
+pub mod partition;
+pub mod report;
@@ -0,0 +1,38 @@
+use std::collections::BTreeSet;
+use crate::analyser::graph::ClassGraph;
+
+/// Graphviz rendering of a class graph: project classes are grouped in one
+/// cluster per package, anything else is drawn dashed.
+pub fn class_graph_to_dot(graph: &ClassGraph, name: &str) -> String {
+    let mut dot = format!("digraph \"{}\" {{\n    rankdir=LR;\n    node [shape=box];\n", escape(name));
+
+    for (i, package) in graph.packages().into_iter().enumerate() {
+        dot.push_str(&format!("    subgraph cluster_{i} {{\n        label=\"{}\";\n", escape(package)));
+        for (class, _) in graph.classes.iter().filter(|(_, p)| p.as_str() == package) {
+            dot.push_str(&format!("        \"{}\";\n", escape(class)));
+        }
+        dot.push_str("    }\n");
+    }
+
+    let external: BTreeSet<&str> = graph.edges.keys()
+        .flat_map(|(from, to)| [from.as_str(), to.as_str()])
+        .filter(|node| !graph.is_project_class(node))
+        .collect();
+    for node in external {
+        dot.push_str(&format!("    \"{}\" [style=dashed];\n", escape(node)));
+    }
+
+    for ((from, to), kinds) in &graph.edges {
+        let label: Vec<String> = kinds.iter().map(|kind| kind.to_string()).collect();
+        dot.push_str(&format!(
+            "    \"{}\" -> \"{}\" [label=\"{}\"];\n",
+            escape(from), escape(to), label.join(",")
+        ));
+    }
+    dot.push_str("}\n");
+    dot
+}
+
+pub fn escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,56 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::analyser::graph::ClassGraph;
+use crate::export::dot::class_graph_to_dot;
+
+/// Name of the diagram file of a package, `_default` standing for the default package.
+pub fn partition_file_name(package: &str) -> String {
+    match package.is_empty() {
+        true => String::from("_default.dot"),
+        false => format!("{package}.dot"),
+    }
+}
+
+/// Writes one DOT diagram per package in `out_dir`, each holding the package
+/// classes and their direct neighbours, plus an `index.html` linking them.
+/// Returns the written diagrams.
+pub fn export_package_partitions(graph: &ClassGraph, out_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    if let Err(e) = fs::create_dir_all(out_dir) {
+        return Err(format!("Failed to create {}: {}", out_dir.display(), e));
+    }
+
+    let mut written = Vec::new();
+    let mut rows = String::new();
+    for package in graph.packages() {
+        let partition = graph.package_partition(package);
+        let file_name = partition_file_name(package);
+        let file = out_dir.join(&file_name);
+        if let Err(e) = fs::write(&file, class_graph_to_dot(&partition, package)) {
+            return Err(format!("Failed to write {}: {}", file.display(), e));
+        }
+        written.push(file);
+
+        rows.push_str(&format!(
+            "      <tr><td><a href=\"{}\">{}</a></td><td>{}</td><td>{}</td></tr>\n",
+            html_escape(&file_name),
+            html_escape(if package.is_empty() { "(default package)" } else { package }),
+            partition.classes.len(),
+            partition.edges.len(),
+        ));
+    }
+
+    let index = format!(
+        "<!DOCTYPE html>\n<html>\n  <head><meta charset=\"utf-8\"><title>Package diagrams</title></head>\n  <body>\n    \
+        <h1>Package diagrams</h1>\n    <table>\n      <tr><th>Package</th><th>Classes</th><th>Edges</th></tr>\n{rows}    \
+        </table>\n  </body>\n</html>\n"
+    );
+    let index_file = out_dir.join("index.html");
+    if let Err(e) = fs::write(&index_file, index) {
+        return Err(format!("Failed to write {}: {}", index_file.display(), e));
+    }
+    Ok(written)
+}
+
+pub fn html_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
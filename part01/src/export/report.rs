@@ -0,0 +1,162 @@
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::analyser::graph::ClassGraph;
+use crate::analyser::index::{flatten_classes, ProjectIndex};
+use crate::common::types::{DependencyKind, ProjectDepsReport};
+use crate::export::dot::class_graph_to_dot;
+use crate::export::partition::html_escape;
+
+/// Artifacts `export_report` can write, each to `dependencies.<format>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ExportFormat {
+    /// The whole project report, classes included
+    Json,
+    /// The class graph, see [`class_graph_to_dot`]
+    Dot,
+    /// A page with the size and coupling of every class and a table of the
+    /// edges, showing the code behind them when snippets were captured
+    Html,
+    /// One `class,target,kind,file,line` row per edge
+    Csv,
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(ExportFormat::Json),
+            "dot" => Ok(ExportFormat::Dot),
+            "html" => Ok(ExportFormat::Html),
+            "csv" => Ok(ExportFormat::Csv),
+            _ => Err(format!("Unknown export format {}, expected json, dot, html or csv", s)),
+        }
+    }
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ExportFormat::Json => write!(f, "json"),
+            ExportFormat::Dot => write!(f, "dot"),
+            ExportFormat::Html => write!(f, "html"),
+            ExportFormat::Csv => write!(f, "csv"),
+        }
+    }
+}
+
+/// An edge with its target resolved against the project classes.
+struct EdgeRow {
+    class: String,
+    target: String,
+    kind: DependencyKind,
+    file: String,
+    line: usize,
+    snippet: Option<String>,
+}
+
+/// Writes every requested format of a single analysis run in `out_dir`, so a
+/// report pipeline needing several artifacts analyses the project once.
+/// Returns the written files.
+pub fn export_report(report: &ProjectDepsReport, formats: &[ExportFormat], out_dir: &Path) -> Result<Vec<PathBuf>, String> {
+    if let Err(e) = fs::create_dir_all(out_dir) {
+        return Err(format!("Failed to create {}: {}", out_dir.display(), e));
+    }
+
+    let mut formats = formats.to_vec();
+    formats.sort();
+    formats.dedup();
+
+    let mut written = Vec::new();
+    for format in formats {
+        let contents = match format {
+            ExportFormat::Json => match serde_json::to_string_pretty(report) {
+                Ok(json) => json,
+                Err(e) => return Err(format!("Failed to serialize the report: {}", e)),
+            },
+            ExportFormat::Dot => class_graph_to_dot(&ClassGraph::build(&report.classes), "dependencies"),
+            ExportFormat::Html => report_to_html(report, &edge_rows(report)),
+            ExportFormat::Csv => edges_to_csv(&edge_rows(report)),
+        };
+        let file = out_dir.join(format!("dependencies.{format}"));
+        if let Err(e) = fs::write(&file, contents) {
+            return Err(format!("Failed to write {}: {}", file.display(), e));
+        }
+        written.push(file);
+    }
+    Ok(written)
+}
+
+fn edge_rows(report: &ProjectDepsReport) -> Vec<EdgeRow> {
+    let index = ProjectIndex::new(&report.classes);
+    let mut rows = Vec::new();
+    for (class, top_level) in flatten_classes(&report.classes) {
+        for edge in &class.edges {
+            rows.push(EdgeRow {
+                class: class.qualified_name(),
+                target: index.resolve(class, top_level, &edge.target),
+                kind: edge.kind,
+                file: class.source_file.clone(),
+                line: edge.line,
+                snippet: edge.snippet.clone(),
+            });
+        }
+    }
+    rows
+}
+
+fn edges_to_csv(rows: &[EdgeRow]) -> String {
+    let mut csv = String::from("class,target,kind,file,line\n");
+    for row in rows {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            csv_field(&row.class), csv_field(&row.target), row.kind, csv_field(&row.file), row.line
+        ));
+    }
+    csv
+}
+
+/// Quotes the field when it holds a separator, generic targets like `Map<K,V>` do.
+fn csv_field(text: &str) -> String {
+    match text.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", text.replace('"', "\"\"")),
+        false => text.to_string(),
+    }
+}
+
+fn report_to_html(report: &ProjectDepsReport, rows: &[EdgeRow]) -> String {
+    let mut classes = String::new();
+    for (class, _) in flatten_classes(&report.classes) {
+        classes.push_str(&format!(
+            "      <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+            html_escape(&class.qualified_name()),
+            class.size.lines,
+            class.size.methods,
+            class.size.fields,
+            class.class_deps.len(),
+        ));
+    }
+
+    let mut edges = String::new();
+    for row in rows {
+        edges.push_str(&format!(
+            "      <tr><td>{}</td><td>{}</td><td>{}</td><td>{}:{}</td><td><code>{}</code></td></tr>\n",
+            html_escape(&row.class),
+            html_escape(&row.target),
+            row.kind,
+            html_escape(&row.file),
+            row.line,
+            html_escape(row.snippet.as_deref().unwrap_or("")),
+        ));
+    }
+    format!(
+        "<!DOCTYPE html>\n<html>\n  <head><meta charset=\"utf-8\"><title>Dependencies</title></head>\n  <body>\n    \
+        <h1>Classes</h1>\n    <table>\n      \
+        <tr><th>Class</th><th>Lines</th><th>Methods</th><th>Fields</th><th>Dependencies</th></tr>\n{classes}    \
+        </table>\n    <h1>Dependencies</h1>\n    <table>\n      \
+        <tr><th>Class</th><th>Target</th><th>Kind</th><th>Source</th><th>Code</th></tr>\n{edges}    \
+        </table>\n  </body>\n</html>\n"
+    )
+}
@@ -0,0 +1,161 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Shape of a generated Java test project.
+#[derive(Debug, Clone)]
+pub struct FixtureSpec {
+    /// Root package of the project
+    pub base_package: String,
+    /// Levels of sub-packages below the root one
+    pub depth: usize,
+    /// Sub-packages of every package
+    pub branching: usize,
+    pub classes_per_package: usize,
+    /// Closes the class chain of each package into a ring and makes every
+    /// package depend back on its first sub-package
+    pub cycles: bool,
+    /// Levels of static nested classes inside every class
+    pub nested_depth: usize,
+}
+
+impl Default for FixtureSpec {
+    fn default() -> Self {
+        Self {
+            base_package: String::from("fixture"),
+            depth: 2,
+            branching: 2,
+            classes_per_package: 3,
+            cycles: false,
+            nested_depth: 0,
+        }
+    }
+}
+
+/// What was written by [`generate_fixture`].
+#[derive(Debug, Default)]
+pub struct FixtureSummary {
+    pub packages: usize,
+    pub files: usize,
+}
+
+struct Package {
+    name: String,
+    classes: Vec<String>,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+/// Writes a Java project following `spec` under `out_dir`, one file per class.
+///
+/// Classes are named `Type0`, `Type1`... across the whole project, so the output
+/// only depends on the spec. Each class uses the next one of its package, the
+/// first class of the parent package, and is instantiated by its predecessor.
+pub fn generate_fixture(out_dir: &Path, spec: &FixtureSpec) -> Result<FixtureSummary, String> {
+    let packages = plan_packages(spec);
+    let mut summary = FixtureSummary { packages: packages.len(), files: 0 };
+
+    for package in &packages {
+        let folder = package.name.split('.').fold(PathBuf::from(out_dir), |dir, part| dir.join(part));
+        if let Err(e) = fs::create_dir_all(&folder) {
+            return Err(format!("Failed to create {}: {}", folder.display(), e));
+        }
+
+        for (i, class_name) in package.classes.iter().enumerate() {
+            let source = class_source(&packages, package, i, spec);
+            let file = folder.join(format!("{class_name}.java"));
+            if let Err(e) = fs::write(&file, source) {
+                return Err(format!("Failed to write {}: {}", file.display(), e));
+            }
+            summary.files += 1;
+        }
+    }
+
+    Ok(summary)
+}
+
+fn plan_packages(spec: &FixtureSpec) -> Vec<Package> {
+    let mut packages: Vec<Package> = Vec::new();
+    let mut next_class = 0;
+    // breadth first, so parents always come before their sub-packages
+    let mut pending: Vec<(String, Option<usize>, usize)> = vec![(spec.base_package.clone(), None, 0)];
+    let mut cursor = 0;
+    while cursor < pending.len() {
+        let (name, parent, level) = pending[cursor].clone();
+        let classes = (0..spec.classes_per_package)
+            .map(|i| format!("Type{}", next_class + i))
+            .collect();
+        next_class += spec.classes_per_package;
+
+        let index = packages.len();
+        packages.push(Package { name: name.clone(), classes, parent, children: Vec::new() });
+        if let Some(parent) = parent {
+            packages[parent].children.push(index);
+        }
+        if level < spec.depth {
+            for b in 0..spec.branching {
+                pending.push((format!("{name}.p{b}"), Some(index), level + 1));
+            }
+        }
+        cursor += 1;
+    }
+    packages
+}
+
+fn class_source(packages: &[Package], package: &Package, i: usize, spec: &FixtureSpec) -> String {
+    let class_name = &package.classes[i];
+    let count = package.classes.len();
+    let next = match i + 1 < count {
+        true => Some(&package.classes[i + 1]),
+        false if spec.cycles && count > 1 => Some(&package.classes[0]),
+        false => None,
+    };
+
+    let mut imports = Vec::new();
+    let mut members = Vec::new();
+    if let Some(next) = next {
+        members.push(format!("    private {next} next;"));
+        members.push(format!("    public {next} createNext() {{\n        return new {next}();\n    }}"));
+    }
+
+    let parent = package.parent.map(|p| &packages[p]);
+    if let Some(upper) = parent.and_then(|p| p.classes.first().map(|c| (p, c))) {
+        imports.push(format!("import {}.{};", upper.0.name, upper.1));
+        members.push(format!("    protected void attach({} owner) {{ }}", upper.1));
+    }
+
+    if spec.cycles && i == 0 {
+        let first_child = package.children.first().map(|c| &packages[*c]);
+        if let Some(lower) = first_child.and_then(|p| p.classes.first().map(|c| (p, c))) {
+            imports.push(format!("import {}.{};", lower.0.name, lower.1));
+            members.push(format!("    {} child;", lower.1));
+        }
+    }
+
+    if spec.nested_depth > 0 {
+        members.push(nested_source(class_name, next, 1, spec.nested_depth));
+    }
+
+    let mut source = format!("package {};\n\n", package.name);
+    if !imports.is_empty() {
+        source.push_str(&imports.join("\n"));
+        source.push_str("\n\n");
+    }
+    source.push_str(&format!("public class {class_name} {{\n"));
+    source.push_str(&members.join("\n\n"));
+    source.push_str("\n}\n");
+    source
+}
+
+fn nested_source(outer: &str, sibling: Option<&String>, level: usize, max_level: usize) -> String {
+    let indent = "    ".repeat(level);
+    let mut source = format!("{indent}static class {outer}Inner{level} {{\n");
+    if let Some(sibling) = sibling {
+        source.push_str(&format!("{indent}    {sibling} sibling;\n"));
+    }
+    if level < max_level {
+        source.push_str(&nested_source(outer, sibling, level + 1, max_level));
+        source.push('\n');
+    }
+    source.push_str(&format!("{indent}}}"));
+    source
+}
@@ -0,0 +1,6 @@
+pub mod common;
+pub mod analyser;
+pub mod export;
+#[cfg(unix)]
+pub mod daemon;
+pub mod fixture;
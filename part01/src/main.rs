@@ -1,28 +1,364 @@
-mod common;
-mod analyser;
+mod cli;
 
-use analyser::dependency_analyser_lib::get_class_dependencies;
-use crate::analyser::dependency_analyser_lib::{get_package_dependencies, get_project_dependencies};
+use std::io::stdout;
+use std::path::{Path, PathBuf};
+use clap::Parser;
+use assignment_02_part01::analyser::dependency_analyser_lib::{
+    get_class_dependencies_with_options,
+    get_package_dependencies, get_package_dependencies_with_options, get_project_dependencies,
+    get_project_dependencies_with_options, stream_project_dependencies,
+};
+use assignment_02_part01::analyser::api_surface::api_surface;
+use assignment_02_part01::analyser::call_graph::CallGraph;
+use assignment_02_part01::analyser::config::ProjectConfig;
+use assignment_02_part01::analyser::graph::ClassGraph;
+use assignment_02_part01::analyser::options::AnalysisOptions;
+use assignment_02_part01::analyser::rules::check_rules;
+use assignment_02_part01::common::format::{render, render_methods, ReportFormat};
+use assignment_02_part01::common::types::FileDiagnostic;
+use assignment_02_part01::export::partition::export_package_partitions;
+use assignment_02_part01::export::report::{export_report, ExportFormat};
+use assignment_02_part01::fixture::{generate_fixture, FixtureSpec};
+#[cfg(unix)]
+use assignment_02_part01::daemon::{self, DaemonRequest};
+use assignment_02_part01::analyser::sink::{TeeSink, WriterSink};
+use assignment_02_part01::analyser::workspace::get_workspace_dependencies;
+use crate::cli::{CallGraphArgs, CallGraphFormat, Cli, Command, GenFixtureArgs, ProjectArgs};
+#[cfg(unix)]
+use crate::cli::{DaemonAction, DaemonArgs};
 
 #[tokio::main]
 async fn main() {
+    let cli = Cli::parse();
+
+    match cli.command {
+        None => run_test_files().await,
+        Some(Command::Class { file, format, methods: true, using }) => print_methods(file, using, format).await,
+        Some(Command::Class { file, format, .. }) => print_class(file, format).await,
+        Some(Command::Package { folder, recursive }) => print_package(folder, recursive).await,
+        Some(Command::Project(args)) => print_project(args).await,
+        Some(Command::Partition { roots, out }) => export_partitions(roots, out).await,
+        Some(Command::Export { roots, formats, out, snippets }) => export_reports(roots, formats, out, snippets).await,
+        Some(Command::Workspace { folder, json }) => print_workspace(folder, json).await,
+        Some(Command::ApiSurface { roots, json }) => print_api_surface(roots, json).await,
+        Some(Command::CallGraph(args)) => print_call_graph(args).await,
+        Some(Command::GenFixture(args)) => gen_fixture(args),
+        #[cfg(unix)]
+        Some(Command::Daemon(args)) => run_daemon(args).await,
+    }
+}
+
+async fn run_test_files() {
     println!("Starting program:");
 
-    match get_class_dependencies("src/test_files/src/main/java/pcd/ass02/MyClass.java".to_string()).await {
-        Ok(report) => {
-            for r in report {
-                println!("{r}");
+    print_class("src/test_files/src/main/java/pcd/ass02/MyClass.java".to_string(), ReportFormat::Pretty).await;
+
+    match get_package_dependencies("src/test_files/src/main/java/pcd/ass02".to_string()).await {
+        Ok(report) => println!("{:?}", report),
+        Err(e) => println!("{}", e)
+    }
+    match get_project_dependencies("src/test_files".to_string()).await {
+        Ok(report) => println!("{:?}", report.project_deps),
+        Err(e) => println!("{}", e)
+    }
+}
+
+async fn print_class(file: String, format: ReportFormat) {
+    let options = configured_options(&file);
+    match get_class_dependencies_with_options(file, &options).await {
+        Ok(report) => print!("{}", render(&report, format)),
+        Err(e) => exit_with_error(e),
+    }
+}
+
+async fn print_methods(file: String, using: Option<String>, format: ReportFormat) {
+    let options = AnalysisOptions { per_method: true, ..configured_options(&file) };
+    match get_class_dependencies_with_options(file, &options).await {
+        Ok(report) => print!("{}", render_methods(&report, using.as_deref(), format)),
+        Err(e) => exit_with_error(e),
+    }
+}
+
+/// The `.depanalyzer.toml` applying to `path`, an invalid file being fatal.
+fn discover_config(path: &str) -> ProjectConfig {
+    match ProjectConfig::discover(Path::new(path)) {
+        Ok(Some((file, config))) => {
+            eprintln!("Using {}", file.display());
+            config
+        },
+        Ok(None) => ProjectConfig::default(),
+        // a broken file would otherwise silently drop every rule and exclude
+        Err(e) => exit_with_error(e),
+    }
+}
+
+/// Options of the commands without flags of their own, set from the config
+/// file applying to `path`.
+fn configured_options(path: &str) -> AnalysisOptions {
+    let mut options = AnalysisOptions::default();
+    discover_config(path).apply(&mut options);
+    options
+}
+
+fn load_config(args: &ProjectArgs) -> ProjectConfig {
+    let mut config = match args.no_config {
+        true => ProjectConfig::default(),
+        false => discover_config(&args.roots[0]),
+    };
+
+    if !args.exclude.is_empty() {
+        config.exclude = args.exclude.clone();
+    }
+    if !args.extra_extensions.is_empty() {
+        config.extra_extensions = args.extra_extensions.clone();
+    }
+    if !args.stdlib_prefixes.is_empty() {
+        config.stdlib_prefixes = args.stdlib_prefixes.clone();
+    }
+    if args.max_class_deps.is_some() {
+        config.thresholds.max_class_dependencies = args.max_class_deps;
+    }
+    if args.max_file_size.is_some() {
+        config.max_file_size = args.max_file_size;
+    }
+    config.output.stream |= args.stream;
+    if let Some(format) = args.format {
+        config.output.format = format;
+    }
+    config
+}
+
+async fn print_package(folder: String, recursive: bool) {
+    let options = AnalysisOptions { recursive, ..configured_options(&folder) };
+    match get_package_dependencies_with_options(folder, &options).await {
+        Ok(report) if recursive => {
+            for package in report.flatten() {
+                println!("{}: {:?}", package.package_name, package.package_deps);
+                for diagnostic in &package.diagnostics {
+                    eprintln!("  {diagnostic}");
+                }
             }
+            println!("all: {:?}", report.all_dependencies());
         },
-        Err(e) => eprintln!("Error: {}", e),
+        Ok(report) => println!("{:?}", report),
+        Err(e) => exit_with_error(e),
     }
+}
+
+async fn print_project(args: ProjectArgs) {
+    let config = load_config(&args);
+    let mut options = AnalysisOptions { retain_classes: true, ..AnalysisOptions::default() };
+    config.apply(&mut options);
+
+    let format = config.output.format;
+    let violations = if config.output.stream {
+        let mut writer = WriterSink::with_format(stdout(), format);
+        // reports are only kept when there are rules to check them against
+        let (result, classes) = match config.has_rules() {
+            true => {
+                let mut sink = TeeSink::new(&mut writer);
+                let result = stream_project_dependencies(args.roots, &options, &mut sink).await;
+                (result, sink.classes)
+            },
+            false => (stream_project_dependencies(args.roots, &options, &mut writer).await, Vec::new()),
+        };
+        match result {
+            Ok(report) => {
+                if format == ReportFormat::Pretty {
+                    println!("{:?}", report.project_deps);
+                }
+                print_diagnostics(&report.diagnostics);
+            },
+            Err(e) => exit_with_error(e),
+        }
+        check_rules(&classes, &config)
+    } else {
+        match get_project_dependencies_with_options(args.roots, &options).await {
+            Ok(report) => {
+                print!("{}", render(&report.classes, format));
+                if format == ReportFormat::Pretty {
+                    println!("{:?}", report.project_deps);
+                }
+                print_diagnostics(&report.diagnostics);
+                check_rules(&report.classes, &config)
+            },
+            Err(e) => exit_with_error(e),
+        }
+    };
 
-    // match get_package_dependencies("src/test_files".to_string()).await {
-    //     Ok(report) => println!("{:?}", report),
-    //     Err(e) => println!("{}", e)
-    // }
-    // match get_project_dependencies("src/test_files".to_string()).await {
-    //     Ok(report) => println!("{:?}", report),
-    //     Err(e) => println!("{}", e)
-    // }
+    // kept out of stdout in the plain format, which only lists dependencies
+    for violation in &violations {
+        match format {
+            ReportFormat::Pretty => println!("violation: {violation}"),
+            ReportFormat::Plain => eprintln!("violation: {violation}"),
+        }
+    }
+    if !violations.is_empty() {
+        std::process::exit(1);
+    }
+}
+
+/// Problems that did not stop the analysis, on stderr so that reports stay parseable.
+fn print_diagnostics(diagnostics: &[FileDiagnostic]) {
+    for diagnostic in diagnostics {
+        eprintln!("warning: {diagnostic}");
+    }
+}
+
+fn exit_with_error(message: impl std::fmt::Display) -> ! {
+    eprintln!("Error: {}", message);
+    std::process::exit(1);
+}
+
+async fn export_partitions(roots: Vec<String>, out: String) {
+    let options = AnalysisOptions { retain_classes: true, ..configured_options(&roots[0]) };
+    let report = match get_project_dependencies_with_options(roots, &options).await {
+        Ok(report) => report,
+        Err(e) => exit_with_error(e),
+    };
+
+    let graph = ClassGraph::build(&report.classes);
+    match export_package_partitions(&graph, Path::new(&out)) {
+        Ok(files) => println!("Wrote {} package diagrams and index.html to {}", files.len(), out),
+        Err(e) => exit_with_error(e),
+    }
+}
+
+async fn export_reports(roots: Vec<String>, formats: Vec<ExportFormat>, out: String, snippets: bool) {
+    let options = AnalysisOptions { snippets, retain_classes: true, ..configured_options(&roots[0]) };
+    let report = match get_project_dependencies_with_options(roots, &options).await {
+        Ok(report) => report,
+        Err(e) => exit_with_error(e),
+    };
+
+    match export_report(&report, &formats, Path::new(&out)) {
+        Ok(files) => {
+            for file in files {
+                println!("Wrote {}", file.display());
+            }
+        },
+        Err(e) => exit_with_error(e),
+    }
+}
+
+async fn print_workspace(folder: String, json: bool) {
+    let report = match get_workspace_dependencies(folder).await {
+        Ok(report) => report,
+        Err(e) => exit_with_error(e),
+    };
+
+    if json {
+        match serde_json::to_string_pretty(&report) {
+            Ok(json) => println!("{json}"),
+            Err(e) => exit_with_error(e),
+        }
+        return;
+    }
+    for repository in &report.repositories {
+        println!(
+            "{}: {} classes, {} dependencies",
+            repository.name, repository.report.classes.len(), repository.report.project_deps.len()
+        );
+        print_diagnostics(&repository.report.diagnostics);
+    }
+    print_diagnostics(&report.diagnostics);
+    for edge in &report.edges {
+        println!("{} -> {} ({} uses)", edge.from_repository, edge.to_repository, edge.uses.len());
+        for used in &edge.uses {
+            println!("  {used}");
+        }
+    }
+}
+
+async fn print_api_surface(roots: Vec<String>, json: bool) {
+    let options = AnalysisOptions { retain_classes: true, ..configured_options(&roots[0]) };
+    let report = match get_project_dependencies_with_options(roots, &options).await {
+        Ok(report) => report,
+        Err(e) => exit_with_error(e),
+    };
+
+    let surfaces = api_surface(&ClassGraph::build(&report.classes));
+    if json {
+        match serde_json::to_string_pretty(&surfaces) {
+            Ok(json) => println!("{json}"),
+            Err(e) => exit_with_error(e),
+        }
+        return;
+    }
+    for surface in surfaces {
+        println!("{}", surface.package);
+        for exposed in &surface.exposed {
+            println!("  exposed  {} (used by {})", exposed.class, exposed.used_by.join(", "));
+        }
+        for internal in &surface.internal {
+            println!("  internal {internal}");
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn run_daemon(args: DaemonArgs) {
+    let socket = args.socket.map(PathBuf::from).unwrap_or_else(daemon::default_socket_path);
+    let request = match args.action {
+        DaemonAction::Serve { roots } => {
+            println!("Listening on {}", socket.display());
+            if let Err(e) = daemon::serve(&socket, roots).await {
+                exit_with_error(e);
+            }
+            return;
+        },
+        DaemonAction::Status => DaemonRequest::Status,
+        DaemonAction::Analyze { roots } => {
+            // the daemon may run from another folder
+            let roots: Vec<String> = roots.iter()
+                .map(|root| std::path::absolute(root).map(|path| path.to_string_lossy().into_owned()).unwrap_or_else(|_| root.clone()))
+                .collect();
+            DaemonRequest::Analyze { roots }
+        },
+        DaemonAction::Query { class } => DaemonRequest::Query { class },
+        DaemonAction::Stop => DaemonRequest::Stop,
+    };
+
+    match daemon::request(&socket, &request).await {
+        Ok(answer) => print!("{answer}"),
+        Err(e) => exit_with_error(e),
+    }
+}
+
+async fn print_call_graph(args: CallGraphArgs) {
+    let options = AnalysisOptions { call_graph: true, retain_classes: true, ..configured_options(&args.roots[0]) };
+    let report = match get_project_dependencies_with_options(args.roots, &options).await {
+        Ok(report) => report,
+        Err(e) => exit_with_error(e),
+    };
+
+    let graph = CallGraph::build(&report.classes);
+    if let Some(method) = args.impact {
+        for impacted in graph.impacted_by(&method) {
+            println!("{impacted}");
+        }
+        return;
+    }
+    match args.format {
+        CallGraphFormat::Dot => print!("{}", graph.to_dot()),
+        CallGraphFormat::Json => match graph.to_json() {
+            Ok(json) => println!("{json}"),
+            Err(e) => exit_with_error(e),
+        },
+    }
+}
+
+fn gen_fixture(args: GenFixtureArgs) {
+    let spec = FixtureSpec {
+        base_package: args.package,
+        depth: args.depth,
+        branching: args.branching,
+        classes_per_package: args.classes,
+        cycles: args.cycles,
+        nested_depth: args.nested,
+    };
+    match generate_fixture(Path::new(&args.out), &spec) {
+        Ok(summary) => println!("Generated {} files in {} packages under {}", summary.files, summary.packages, args.out),
+        Err(e) => exit_with_error(e),
+    }
 }
@@ -1,10 +1,11 @@
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 use xmltree::{Element as XMLElement, XMLNode};
 use iced::{Element, Length, Subscription, Task};
-use iced::widget::{button, container, svg, text_input, Column, Row, Scrollable, Text};
-use crate::dependency::build_dependency_graph;
+use iced::widget::{button, container, svg, text_input, tooltip, Column, Row, Scrollable, Text};
+use crate::dependency::{analyse_project, build_dependency_graph, ProjectAnalysis};
+use crate::layout::LayoutCache;
 use iced::futures::stream;
 
 use tokio::sync::watch;
@@ -16,24 +17,45 @@ pub enum Message {
     AskDependency,
     DependencyReceived(Result<(), String>),
     ProjectDependenciesUpdated,
-    ImageGenerated(svg::Handle),
+    /// The Mermaid rendering of the graph, with the edges it was rendered from
+    ImageGenerated(XMLElement, HashSet<(String, String)>),
+    ProjectAnalysed(Result<ProjectAnalysis, String>),
 }
 
 #[derive(Clone)]
 pub struct AppState {
     project_dependencies: Arc<RwLock<HashSet<(String, String)>>>,
+    /// Source root of every analysed class
+    class_roots: Arc<RwLock<HashMap<String, PathBuf>>>,
     input_value: String,
     notifier: watch::Sender<()>,
     handle: Option<svg::Handle>,
+    /// Keeps nodes in place while edges stream in and between analyses of the same roots
+    layout: LayoutCache,
+    /// Whether a Mermaid rendering is running, and whether the graph changed since it started
+    rendering: bool,
+    render_pending: bool,
+    roots: Vec<PathBuf>,
+    /// API surface and edge snippets, once the syntax tree analysis is over
+    analysis: ProjectAnalysis,
+    /// Why the syntax tree analysis failed
+    analysis_error: Option<String>,
 }
 
 impl Default for AppState {
     fn default() -> Self {
         Self { 
             project_dependencies: Default::default(), 
+            class_roots: Default::default(),
             input_value: Default::default(), 
             notifier: watch::channel(()).0,
             handle: None,
+            layout: LayoutCache::default(),
+            rendering: false,
+            render_pending: false,
+            roots: Vec::new(),
+            analysis: ProjectAnalysis::default(),
+            analysis_error: None,
         }
     }
 }
@@ -60,7 +82,7 @@ impl AppState {
         let mut deps_column = Column::new().spacing(5).padding(10);
         
         let mut top_row = Row::new().spacing(5).padding(8);
-        top_row = top_row.push(text_input("Enter project path...", &self.input_value).on_input(|x| Message::UpdateInputVal(x)));
+        top_row = top_row.push(text_input("Enter project path(s)...", &self.input_value).on_input(|x| Message::UpdateInputVal(x)));
         top_row = top_row.push(
             match self.input_value.is_empty() {
                 true => button("Analyze"),
@@ -68,9 +90,41 @@ impl AppState {
             }
         );
 
+        let class_roots = self.class_roots.read().unwrap();
         for (to, into) in self.project_dependencies.read().unwrap().clone() {
-            let s = format!("{to} -> {into}");
-            deps_column = deps_column.push(Text::new(s));
+            let s = match class_roots.get(&to) {
+                Some(root) => format!("{to} -> {into}  ({})", root.display()),
+                None => format!("{to} -> {into}"),
+            };
+            // the line behind the edge, when the syntax tree analysis found it
+            deps_column = match self.analysis.snippets.get(&(to, into)) {
+                Some(snippet) => deps_column.push(tooltip(
+                    Text::new(s),
+                    container(Text::new(snippet.clone())).padding(5).style(container::rounded_box),
+                    tooltip::Position::FollowCursor,
+                )),
+                None => deps_column.push(Text::new(s)),
+            };
+        }
+
+        let mut metrics_column = Column::new().spacing(5).padding(10);
+        if let Some(error) = &self.analysis_error {
+            metrics_column = metrics_column.push(Text::new(format!("API surface unavailable: {error}")));
+        }
+        for surface in &self.analysis.api_surface {
+            metrics_column = metrics_column.push(Text::new(format!(
+                "{}: {} exposed, {} internal",
+                surface.package,
+                surface.exposed.len(),
+                surface.internal.len()
+            )));
+            for exposed in &surface.exposed {
+                metrics_column = metrics_column.push(Text::new(format!(
+                    "    {} <- {}",
+                    exposed.class,
+                    exposed.used_by.join(", ")
+                )));
+            }
         }
 
         let displayed_image = match &self.handle {
@@ -92,7 +146,14 @@ impl AppState {
             .width(Length::Fill)
             .spacing(10);
 
-        container(Column::new().push(top_row).push(scroll_image).push(scroll).spacing(10))
+        let scroll_metrics = Scrollable::new(metrics_column)
+            .height(Length::Fill)
+            .width(Length::Fill)
+            .spacing(10);
+
+        let bottom_row = Row::new().push(scroll).push(scroll_metrics).spacing(10);
+
+        container(Column::new().push(top_row).push(scroll_image).push(bottom_row).spacing(10))
             .width(Length::Fill)
             .height(Length::Fill)
             .padding(20)
@@ -107,33 +168,68 @@ impl AppState {
             }
             Message::AskDependency => {
                 self.project_dependencies.write().unwrap().clear();
+                self.class_roots.write().unwrap().clear();
                 self.handle = None;
+                self.analysis = ProjectAnalysis::default();
+                self.analysis_error = None;
 
-                let path = PathBuf::from(self.input_value.clone());
-                if !path.exists() {
+                // several source roots can be given, separated like in the PATH variable
+                let roots: Vec<PathBuf> = std::env::split_paths(&self.input_value).collect();
+                if roots.is_empty() || roots.iter().any(|root| !root.exists()) {
                     return Task::none();
                 }
+                if roots != self.roots {
+                    self.layout.clear();
+                }
+                self.roots = roots.clone();
                 
                 let deps_borr = self.project_dependencies.clone();
+                let roots_borr = self.class_roots.clone();
                 let notifier_borr = self.notifier.clone();
 
                 Task::perform(async move {
-                    build_dependency_graph(path.clone(), deps_borr, notifier_borr).await
+                    build_dependency_graph(roots, deps_borr, roots_borr, notifier_borr).await
                 }, Message::DependencyReceived)
             }
             Message::DependencyReceived(_res) => {
-                let deps_borr = self.project_dependencies.clone();
-                Task::perform(image_generation(deps_borr), Message::ImageGenerated)
+                Task::batch([
+                    self.render_graph(),
+                    Task::perform(analyse_project(self.roots.clone()), Message::ProjectAnalysed),
+                ])
             },
-            Message::ImageGenerated(res) => {
-                self.handle = Some(res);
-                // This is where you would update the image in the UI
-                // For now, we just return none
+            Message::ImageGenerated(mut root, edges) => {
+                self.rendering = false;
+                self.layout.apply(&mut root, &edges);
+                let mut output = Vec::new();
+                root.write(&mut output).unwrap();
+                self.handle = Some(svg::Handle::from_memory(output));
+                match std::mem::take(&mut self.render_pending) {
+                    true => self.render_graph(),
+                    false => Task::none(),
+                }
+            }
+            Message::ProjectAnalysed(res) => {
+                match res {
+                    Ok(analysis) => self.analysis = analysis,
+                    Err(e) => self.analysis_error = Some(e),
+                }
                 Task::none()
             }
-            Message::ProjectDependenciesUpdated => Task::none()
+            Message::ProjectDependenciesUpdated => self.render_graph(),
         }
     }
+
+    /// Renders the graph again, nodes already drawn keeping their position once
+    /// the rendering is done. Changes arriving meanwhile are rendered next.
+    fn render_graph(&mut self) -> Task<Message> {
+        if self.rendering {
+            self.render_pending = true;
+            return Task::none();
+        }
+        self.rendering = true;
+        let edges = self.project_dependencies.read().unwrap().clone();
+        Task::perform(image_generation(edges), |(root, edges)| Message::ImageGenerated(root, edges))
+    }
 }
 
 fn process_element(elem: &mut XMLElement) {
@@ -169,19 +265,17 @@ fn process_element(elem: &mut XMLElement) {
     });
 }
 
-async fn image_generation(project_dependencies: Arc<RwLock<HashSet<(String, String)>>>) -> svg::Handle {
+async fn image_generation(edges: HashSet<(String, String)>) -> (XMLElement, HashSet<(String, String)>) {
     let mermaid = Mermaid::new().unwrap();
     let mut graph = String::from("graph LR\n");
-    for el in project_dependencies.read().unwrap().iter() {
-        if el.0.contains("\"") || el.1.contains("\"") {
-            continue;
-        }
+    let edges: HashSet<(String, String)> = edges.into_iter()
+        .filter(|el| !el.0.contains("\"") && !el.1.contains("\""))
+        .collect();
+    for el in edges.iter() {
         graph.push_str(&format!("{} --> {}\n", el.0, el.1));
     }
     let svg = mermaid.render(&graph).unwrap();
     let mut root = XMLElement::parse(svg.as_bytes()).unwrap();
     process_element(&mut root);
-    let mut output = Vec::new();
-    root.write(&mut output).unwrap();
-    svg::Handle::from_memory(output)
-}
\ No newline at end of file
+    (root, edges)
+}
@@ -1,6 +1,13 @@
 use std::{
-    collections::HashSet, fs::File, io::{self, BufRead}, path::{Path, PathBuf}, sync::{Arc, RwLock},
+    collections::{HashMap, HashSet}, fs::File, io::{self, BufRead}, path::{Path, PathBuf}, sync::{Arc, RwLock},
 };
+use assignment_02_part01::analyser::api_surface::{api_surface, PackageApiSurface};
+use assignment_02_part01::analyser::config::ProjectConfig;
+use assignment_02_part01::analyser::dependency_analyser_lib::get_project_dependencies_with_options;
+use assignment_02_part01::analyser::graph::ClassGraph;
+use assignment_02_part01::analyser::index::flatten_classes;
+use assignment_02_part01::analyser::options::{is_excluded, AnalysisOptions};
+use assignment_02_part01::analyser::suppressions::Suppressions;
 use lazy_static::lazy_static;
 use regex::Regex;
 use walkdir::WalkDir;
@@ -21,10 +28,12 @@ lazy_static! {
     };
 }
 
-/// Walk directory, find .java files, and build the graph
+/// Walk the source roots, find .java files, and build the graph. Every class
+/// found is recorded in `class_roots` with the root it was found under.
 pub async fn build_dependency_graph(
-    root: PathBuf, 
+    roots: Vec<PathBuf>, 
     project_dependencies: Arc<RwLock<HashSet<(String, String)>>>, 
+    class_roots: Arc<RwLock<HashMap<String, PathBuf>>>,
     watcher: tokio::sync::watch::Sender<()>) -> Result<(), String> {
 
     // Regex for package/import
@@ -39,24 +48,76 @@ pub async fn build_dependency_graph(
         r"[\w<>.\[\]]+\s+\w+\s*\(([^)]*)\)"
     ).unwrap();
 
-    for entry in WalkDir::new(root).into_iter().filter_map(Result::ok) {
-        let path: PathBuf = entry.path().to_path_buf();
-        if path.extension().and_then(|s| s.to_str()) == Some("java") {
-            process_java_file(
-                &path,
-                &pkg_re,
-                &imp_re,
-                &new_re,
-                &decl_re,
-                &sig_re,
-                project_dependencies.clone(),
-                watcher.clone()
-            ).await.map_err(|e| e.to_string())?;
+    let options = configured_options(&roots)?;
+    let excluded = options.exclude_set()?;
+    let mut ignored = Suppressions::default();
+    ignored.ignore_packages(&options.stdlib_prefixes);
+
+    for root in &roots {
+        for entry in WalkDir::new(root).into_iter().filter_map(Result::ok) {
+            let path: PathBuf = entry.path().to_path_buf();
+            if is_excluded(&excluded, root, &path) {
+                continue;
+            }
+            if path.is_file() && options.is_java_source(&path) {
+                let class = process_java_file(
+                    &path,
+                    &pkg_re,
+                    &imp_re,
+                    &new_re,
+                    &decl_re,
+                    &sig_re,
+                    &ignored,
+                    project_dependencies.clone(),
+                    watcher.clone()
+                ).await.map_err(|e| e.to_string())?;
+                class_roots.write().unwrap().insert(class, root.clone());
+                watcher.send(()).unwrap_or_else(|_| ());
+            }
         }
     }
     Ok(())
 }
 
+/// Options with the `.depanalyzer.toml` found from the first root applied, the
+/// same way as the command line analyser.
+fn configured_options(roots: &[PathBuf]) -> Result<AnalysisOptions, String> {
+    let config = match roots.first() {
+        Some(root) => ProjectConfig::discover(root)?.map(|(_, config)| config).unwrap_or_default(),
+        None => ProjectConfig::default(),
+    };
+    let mut options = AnalysisOptions::default();
+    config.apply(&mut options);
+    Ok(options)
+}
+
+/// What the syntax tree analyser finds in the source roots, on top of the graph
+/// built line by line.
+#[derive(Debug, Clone, Default)]
+pub struct ProjectAnalysis {
+    /// Per package API surface
+    pub api_surface: Vec<PackageApiSurface>,
+    /// Source line behind each `class -> type` edge, types without their generics
+    pub snippets: HashMap<(String, String), String>,
+}
+
+/// Analyses the source roots with the syntax tree analyser.
+pub async fn analyse_project(roots: Vec<PathBuf>) -> Result<ProjectAnalysis, String> {
+    let options = AnalysisOptions { retain_classes: true, snippets: true, ..configured_options(&roots)? };
+    let roots = roots.iter().map(|root| root.to_string_lossy().into_owned()).collect();
+    let report = get_project_dependencies_with_options(roots, &options).await?;
+
+    let mut snippets = HashMap::new();
+    for (class, _) in flatten_classes(&report.classes) {
+        for edge in &class.edges {
+            if let (Some(snippet), Some(ty)) = (&edge.snippet, normalize_type(&edge.target)) {
+                snippets.entry((class.qualified_name(), ty)).or_insert_with(|| snippet.clone());
+            }
+        }
+    }
+    Ok(ProjectAnalysis { api_surface: api_surface(&ClassGraph::build(&report.classes)), snippets })
+}
+
 /// Normalize a raw type string: remove generics, array markers, var names
 fn normalize_type(raw: &str) -> Option<String> {
     // strip generics: Foo<Bar> => Foo
@@ -73,6 +134,76 @@ fn normalize_type(raw: &str) -> Option<String> {
     }
 }
 
+/// Where a line starts, as far as literals and comments go.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LexState {
+    Code,
+    BlockComment,
+    TextBlock,
+}
+
+/// Drops the comments of a line and empties its string, char and text block
+/// literals, so the regexes only see code. `state` carries block comments and
+/// text blocks over to the following lines.
+fn strip_literals_and_comments(line: &str, state: &mut LexState) -> String {
+    let chars: Vec<char> = line.chars().collect();
+    let mut code = String::with_capacity(line.len());
+    let mut i = 0;
+    while i < chars.len() {
+        let rest = &chars[i..];
+        match *state {
+            LexState::BlockComment => {
+                if rest.starts_with(&['*', '/']) {
+                    *state = LexState::Code;
+                    code.push(' ');
+                    i += 2;
+                } else {
+                    i += 1;
+                }
+            },
+            LexState::TextBlock => {
+                if rest[0] == '\\' {
+                    i += 2;
+                } else if rest.starts_with(&['"', '"', '"']) {
+                    *state = LexState::Code;
+                    code.push_str("\"\"");
+                    i += 3;
+                } else {
+                    i += 1;
+                }
+            },
+            LexState::Code => {
+                if rest.starts_with(&['/', '/']) {
+                    break;
+                } else if rest.starts_with(&['/', '*']) {
+                    *state = LexState::BlockComment;
+                    i += 2;
+                } else if rest.starts_with(&['"', '"', '"']) {
+                    *state = LexState::TextBlock;
+                    i += 3;
+                } else if rest[0] == '"' || rest[0] == '\'' {
+                    let quote = rest[0];
+                    i += 1;
+                    while i < chars.len() && chars[i] != quote {
+                        // skip the escaped character
+                        if chars[i] == '\\' {
+                            i += 1;
+                        }
+                        i += 1;
+                    }
+                    i += 1;
+                    code.push(quote);
+                    code.push(quote);
+                } else {
+                    code.push(rest[0]);
+                    i += 1;
+                }
+            },
+        }
+    }
+    code
+}
+
 async fn process_java_file(
     path: &Path,
     pkg_re: &Regex,
@@ -80,13 +211,17 @@ async fn process_java_file(
     new_re: &Regex,
     decl_re: &Regex,
     sig_re: &Regex,
+    ignored: &Suppressions,
     project_dependencies: Arc<RwLock<HashSet<(String, String)>>>, 
     watcher: tokio::sync::watch::Sender<()>
-) -> io::Result<()> {
+) -> io::Result<String> {
     let file = File::open(path)?;
     let reader = io::BufReader::new(file);
 
     let mut package = String::new();
+    // imports come first in a Java file, so they are all known by the first use
+    let mut imports: Vec<String> = Vec::new();
+    let mut lex_state = LexState::Code;
     let class_name = path
         .file_stem()
         .and_then(|s| s.to_str())
@@ -94,7 +229,8 @@ async fn process_java_file(
         .to_string();
 
     for line in reader.lines() {
-        let line = line?;
+        // class-like names in strings and comments are not dependencies
+        let line = strip_literals_and_comments(&line?, &mut lex_state);
 
         // package
         if package.is_empty() {
@@ -105,8 +241,14 @@ async fn process_java_file(
         }
         // imports
         if let Some(caps) = imp_re.captures(&line) {
+            match caps[0].contains(".*") {
+                true => imports.push(format!("{}.*", &caps[1])),
+                false => imports.push(caps[1].to_string()),
+            }
             if let Some(ty) = normalize_type(&caps[1]) {
                 send_update(
+                    ignored,
+                    &imports,
                     package.clone(), 
                     class_name.clone(), 
                     ty.clone(), 
@@ -120,6 +262,8 @@ async fn process_java_file(
         for caps in new_re.captures_iter(&line) {
             if let Some(ty) = normalize_type(&caps[1]) {
                 send_update(
+                    ignored,
+                    &imports,
                     package.clone(), 
                     class_name.clone(), 
                     ty.clone(), 
@@ -132,6 +276,8 @@ async fn process_java_file(
         for caps in decl_re.captures_iter(&line) {
             if let Some(ty) = normalize_type(&caps[1]) {
                 send_update(
+                    ignored,
+                    &imports,
                     package.clone(), 
                     class_name.clone(), 
                     ty.clone(), 
@@ -149,6 +295,8 @@ async fn process_java_file(
                 if !parts.is_empty() {
                     if let Some(ty) = normalize_type(parts[0]) {
                         send_update(
+                            ignored,
+                            &imports,
                             package.clone(), 
                             class_name.clone(), 
                             ty.clone(), 
@@ -161,21 +309,31 @@ async fn process_java_file(
         }
     }
 
-    Ok(())
+    Ok(qualified_name(&package, &class_name))
+}
+
+fn qualified_name(package: &str, class_name: &str) -> String {
+    if package.is_empty() {
+        class_name.to_string()
+    } else {
+        format!("{}.{}", package, class_name)
+    }
 }
 
 async fn send_update(
+    ignored: &Suppressions,
+    imports: &[String],
     package: String,
     class_name: String, 
     ty: String,
     project_dependencies: Arc<RwLock<HashSet<(String, String)>>>, 
     watcher: tokio::sync::watch::Sender<()>) {
 
-    let fqcn = if package.is_empty() {
-        class_name.clone()
-    } else {
-        format!("{}.{}", package, class_name)
-    };
+    if ignored.is_suppressed(&ty, &package, imports) {
+        return;
+    }
+
+    let fqcn = qualified_name(&package, &class_name);
     {
         let mut deps = project_dependencies.write().unwrap();
         deps.insert((fqcn, ty));
@@ -183,3 +341,33 @@ async fn send_update(
     watcher.send(()).unwrap_or_else(|_| ());
     tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn strip(lines: &[&str]) -> Vec<String> {
+        let mut state = LexState::Code;
+        lines.iter().map(|line| strip_literals_and_comments(line, &mut state)).collect()
+    }
+
+    #[test]
+    fn strings_and_line_comments_are_emptied() {
+        assert_eq!(strip(&[r#"Object s = "com.foo.Bar"; // uses Bar"#]), [r#"Object s = ""; "#]);
+    }
+
+    #[test]
+    fn block_comments_span_lines() {
+        assert_eq!(strip(&["Foo f; /* new Bar()", "   Baz b; */ Qux q;"]), ["Foo f; ", "  Qux q;"]);
+    }
+
+    #[test]
+    fn escaped_quotes_and_chars_stay_inside_the_literal() {
+        assert_eq!(strip(&[r#"s = "a \" new Bar() \\"; c = '"'; d = '\''; Foo f;"#]), [r#"s = ""; c = ''; d = ''; Foo f;"#]);
+    }
+
+    #[test]
+    fn text_blocks_span_lines() {
+        assert_eq!(strip(&[r#"s = """"#, "  new Bar();", r#"  """; Foo f;"#]), ["s = ", "", r#"""; Foo f;"#]);
+    }
+}
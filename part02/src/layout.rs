@@ -0,0 +1,264 @@
+use std::collections::{HashMap, HashSet};
+use xmltree::{Element, XMLNode};
+
+/// Space kept around the graph and between a moved node and the others
+const MARGIN: f32 = 16.0;
+/// Size of a node whose shape could not be read from the Mermaid output
+const DEFAULT_SIZE: (f32, f32) = (120.0, 40.0);
+
+/// Center and size of a node
+type NodeBox = ((f32, f32), (f32, f32));
+
+/// Positions of the nodes of the Mermaid graph by class name, kept between
+/// renders so that a node never moves once drawn. Mermaid lays the whole graph
+/// out again each time: its positions are only kept for the nodes drawn for the
+/// first time, the others going back to where they were.
+#[derive(Debug, Default, Clone)]
+pub struct LayoutCache {
+    /// Center of every node drawn so far, including the ones hidden for a while
+    positions: HashMap<String, (f32, f32)>,
+}
+
+/// A node of the rendered graph, as laid out by Mermaid.
+struct RenderedNode {
+    name: String,
+    center: (f32, f32),
+    size: (f32, f32),
+}
+
+impl LayoutCache {
+    /// Forgets every position, for a new project.
+    pub fn clear(&mut self) {
+        self.positions.clear();
+    }
+
+    /// Moves the nodes of a graph rendered by Mermaid back to their cached
+    /// positions, caches the positions of the new ones, then redraws `edges`
+    /// between the final positions and fits the view box around the nodes.
+    pub fn apply(&mut self, svg: &mut Element, edges: &HashSet<(String, String)>) {
+        let mut nodes = Vec::new();
+        collect_nodes(svg, &mut nodes);
+
+        // known nodes first, so that new ones are moved out of their way
+        let (known, new): (Vec<RenderedNode>, Vec<RenderedNode>) =
+            nodes.into_iter().partition(|node| self.positions.contains_key(&node.name));
+        let mut placed: Vec<NodeBox> = Vec::new();
+        let mut boxes: HashMap<String, NodeBox> = HashMap::new();
+        for node in known {
+            let center = self.positions[&node.name];
+            placed.push((center, node.size));
+            boxes.insert(node.name, (center, node.size));
+        }
+        for node in new {
+            let mut center = node.center;
+            if placed.iter().any(|other| overlaps((center, node.size), *other)) {
+                let bottom = placed.iter().map(|((_, y), (_, h))| y + h / 2.0).fold(f32::MIN, f32::max);
+                center.1 = bottom + MARGIN + node.size.1 / 2.0;
+            }
+            self.positions.insert(node.name.clone(), center);
+            placed.push((center, node.size));
+            boxes.insert(node.name, (center, node.size));
+        }
+
+        move_nodes(svg, &self.positions);
+        redraw_edges(svg, edges, &boxes);
+        fit_view_box(svg, &placed);
+    }
+}
+
+/// The `g.node` groups of the graph, named after the text of their label.
+fn collect_nodes(elem: &Element, nodes: &mut Vec<RenderedNode>) {
+    for child in child_elements(elem) {
+        if child.name == "g" && has_class(child, "node") {
+            if let (Some(name), Some(center)) = (label(child), translate(child)) {
+                nodes.push(RenderedNode { name, center, size: shape_size(child) });
+            }
+        } else {
+            collect_nodes(child, nodes);
+        }
+    }
+}
+
+fn move_nodes(elem: &mut Element, positions: &HashMap<String, (f32, f32)>) {
+    for child in elem.children.iter_mut() {
+        if let XMLNode::Element(child) = child {
+            if child.name == "g" && has_class(child, "node") {
+                if let Some((x, y)) = label(child).and_then(|name| positions.get(&name)) {
+                    child.attributes.insert("transform".into(), format!("translate({x}, {y})"));
+                }
+            } else {
+                move_nodes(child, positions);
+            }
+        }
+    }
+}
+
+/// Replaces the Mermaid edge paths, drawn for its own layout, by straight ones
+/// between the node borders, styled like the first Mermaid one. Edge labels are
+/// dropped as they would be left where Mermaid put them.
+fn redraw_edges(elem: &mut Element, edges: &HashSet<(String, String)>, boxes: &HashMap<String, NodeBox>) {
+    for child in elem.children.iter_mut() {
+        if let XMLNode::Element(child) = child {
+            if child.name == "g" && has_class(child, "edgeLabels") {
+                child.children.clear();
+            } else if child.name == "g" && has_class(child, "edgePaths") {
+                let Some(template) = child_elements(child).find(|path| path.name == "path").cloned() else {
+                    continue;
+                };
+                let mut sorted_edges: Vec<&(String, String)> = edges.iter().collect();
+                sorted_edges.sort();
+                child.children.clear();
+                for (from, to) in sorted_edges {
+                    let (Some(&(from_center, from_size)), Some(&(to_center, to_size))) = (boxes.get(from), boxes.get(to)) else {
+                        continue;
+                    };
+                    if from == to {
+                        continue;
+                    }
+                    let (x1, y1) = border_point(from_center, from_size, to_center);
+                    let (x2, y2) = border_point(to_center, to_size, from_center);
+                    let mut path = template.clone();
+                    path.attributes.remove("id");
+                    path.attributes.insert("d".into(), format!("M{x1},{y1}L{x2},{y2}"));
+                    child.children.push(XMLNode::Element(path));
+                }
+            } else {
+                redraw_edges(child, edges, boxes);
+            }
+        }
+    }
+}
+
+/// Sizes the SVG after the nodes, wherever they ended up.
+fn fit_view_box(svg: &mut Element, placed: &[NodeBox]) {
+    if placed.is_empty() {
+        return;
+    }
+    let min_x = placed.iter().map(|((x, _), (w, _))| x - w / 2.0).fold(f32::MAX, f32::min) - MARGIN;
+    let min_y = placed.iter().map(|((_, y), (_, h))| y - h / 2.0).fold(f32::MAX, f32::min) - MARGIN;
+    let max_x = placed.iter().map(|((x, _), (w, _))| x + w / 2.0).fold(f32::MIN, f32::max) + MARGIN;
+    let max_y = placed.iter().map(|((_, y), (_, h))| y + h / 2.0).fold(f32::MIN, f32::max) + MARGIN;
+    let (width, height) = (max_x - min_x, max_y - min_y);
+    svg.attributes.insert("viewBox".into(), format!("{min_x} {min_y} {width} {height}"));
+    svg.attributes.insert("width".into(), width.to_string());
+    svg.attributes.insert("height".into(), height.to_string());
+    // Mermaid caps the width to its own layout
+    svg.attributes.remove("style");
+}
+
+fn child_elements(elem: &Element) -> impl Iterator<Item = &Element> {
+    elem.children.iter().filter_map(|child| match child {
+        XMLNode::Element(child) => Some(child),
+        _ => None,
+    })
+}
+
+fn has_class(elem: &Element, class: &str) -> bool {
+    elem.attributes.get("class").is_some_and(|classes| classes.split_whitespace().any(|c| c == class))
+}
+
+/// First text below the element, the class name for a node.
+fn label(elem: &Element) -> Option<String> {
+    elem.children.iter().find_map(|child| match child {
+        XMLNode::Text(text) if !text.trim().is_empty() => Some(text.trim().to_string()),
+        XMLNode::Element(child) => label(child),
+        _ => None,
+    })
+}
+
+/// Offset of a `transform="translate(x, y)"` attribute.
+fn translate(elem: &Element) -> Option<(f32, f32)> {
+    let transform = elem.attributes.get("transform")?;
+    let arguments = transform.trim().strip_prefix("translate(")?.strip_suffix(')')?;
+    let mut numbers = arguments.split(|c: char| c == ',' || c.is_whitespace()).filter(|part| !part.is_empty());
+    let x = numbers.next()?.parse().ok()?;
+    let y = numbers.next().map_or(Some(0.0), |y| y.parse().ok())?;
+    Some((x, y))
+}
+
+/// Size of the shape drawn for a node, centered on it.
+fn shape_size(node: &Element) -> (f32, f32) {
+    child_elements(node)
+        .find(|child| child.name == "rect")
+        .and_then(|rect| {
+            let width = rect.attributes.get("width")?.parse().ok()?;
+            let height = rect.attributes.get("height")?.parse().ok()?;
+            Some((width, height))
+        })
+        .unwrap_or(DEFAULT_SIZE)
+}
+
+fn overlaps(((x1, y1), (w1, h1)): NodeBox, ((x2, y2), (w2, h2)): NodeBox) -> bool {
+    (x1 - x2).abs() < (w1 + w2) / 2.0 + MARGIN && (y1 - y2).abs() < (h1 + h2) / 2.0 + MARGIN
+}
+
+/// Where the segment from the center of a node toward `toward` leaves the node box.
+fn border_point((x, y): (f32, f32), (width, height): (f32, f32), (toward_x, toward_y): (f32, f32)) -> (f32, f32) {
+    let (dx, dy) = (toward_x - x, toward_y - y);
+    let scale_x = if dx == 0.0 { f32::INFINITY } else { (width / 2.0) / dx.abs() };
+    let scale_y = if dy == 0.0 { f32::INFINITY } else { (height / 2.0) / dy.abs() };
+    let scale = scale_x.min(scale_y).min(1.0);
+    (x + dx * scale, y + dy * scale)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node(name: &str, x: f32, y: f32) -> String {
+        format!(
+            "<g class=\"node default\" transform=\"translate({x}, {y})\"><rect width=\"60\" height=\"30\"/>\
+            <g class=\"label\"><text>{name}</text></g></g>"
+        )
+    }
+
+    /// A graph shaped like the Mermaid output, once the labels are turned into text.
+    fn rendered(nodes: &[(&str, f32, f32)]) -> Element {
+        let nodes: String = nodes.iter().map(|(name, x, y)| node(name, *x, *y)).collect();
+        let svg = format!(
+            "<svg style=\"max-width: 10px;\" viewBox=\"0 0 10 10\"><g class=\"edgePaths\"><path class=\"flowchart-link\" d=\"M0,0\"/></g>\
+            <g class=\"edgeLabels\"><g class=\"edgeLabel\"/></g><g class=\"nodes\">{nodes}</g></svg>"
+        );
+        Element::parse(svg.as_bytes()).unwrap()
+    }
+
+    fn centers(svg: &Element) -> HashMap<String, (f32, f32)> {
+        let mut nodes = Vec::new();
+        collect_nodes(svg, &mut nodes);
+        nodes.into_iter().map(|node| (node.name, node.center)).collect()
+    }
+
+    fn edges(pairs: &[(&str, &str)]) -> HashSet<(String, String)> {
+        pairs.iter().map(|(from, to)| (from.to_string(), to.to_string())).collect()
+    }
+
+    #[test]
+    fn known_nodes_keep_their_position_and_new_ones_are_placed() {
+        let mut cache = LayoutCache::default();
+        let mut first = rendered(&[("a.A", 50.0, 50.0), ("a.B", 200.0, 50.0)]);
+        cache.apply(&mut first, &edges(&[("a.A", "a.B")]));
+
+        // Mermaid moved everything around and put the new node where a.A was
+        let mut second = rendered(&[("a.A", 300.0, 120.0), ("a.B", 50.0, 120.0), ("a.C", 50.0, 50.0)]);
+        cache.apply(&mut second, &edges(&[("a.A", "a.B"), ("a.C", "a.A")]));
+        let positions = centers(&second);
+        assert_eq!(positions["a.A"], (50.0, 50.0));
+        assert_eq!(positions["a.B"], (200.0, 50.0));
+        assert_eq!(positions["a.C"], (50.0, 96.0));
+        assert_eq!(cache.positions["a.C"], (50.0, 96.0));
+    }
+
+    #[test]
+    fn edges_are_drawn_between_the_final_positions() {
+        let mut cache = LayoutCache::default();
+        let mut svg = rendered(&[("a.A", 50.0, 50.0), ("a.B", 200.0, 50.0)]);
+        cache.apply(&mut svg, &edges(&[("a.A", "a.B")]));
+
+        let paths = svg.get_child("g").unwrap();
+        let drawn: Vec<&str> = child_elements(paths).map(|path| path.attributes["d"].as_str()).collect();
+        assert_eq!(drawn, ["M80,50L170,50"]);
+        assert_eq!(child_elements(paths).next().unwrap().attributes["class"], "flowchart-link");
+        assert_eq!(svg.attributes["viewBox"], "4 19 242 62");
+        assert!(!svg.attributes.contains_key("style"));
+    }
+}
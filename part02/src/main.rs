@@ -1,5 +1,6 @@
 mod dependency;
 mod app_state;
+mod layout;
 
 use crate::app_state::AppState;
 